@@ -0,0 +1,138 @@
+use crate::factors::FactorModel;
+use crate::interactions::Interactions;
+use crate::linalg::solve_spd;
+use crate::rng::Rng;
+
+//settings for implicit-feedback ALS (Hu, Koren and Volinsky, "Collaborative Filtering for Implicit Feedback Datasets")
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlsConfig {
+    pub factors: usize,      //length of each user and song vector
+    pub regularization: f64, //lambda, keeps the vectors small
+    pub alpha: f64,          //confidence in a listen is 1 + alpha * listen_count
+    pub iterations: usize,   //number of times users and songs are both re-solved
+    pub seed: u64,           //seed for the starting vectors, same seed gives the same model
+}
+
+impl Default for AlsConfig {
+    fn default() -> AlsConfig {
+        AlsConfig { factors: 32, regularization: 0.1, alpha: 40.0, iterations: 10, seed: 42 }
+    }
+}
+
+//starting vectors: small random numbers so no two users or songs start out identical
+fn random_factors(rows: usize, factors: usize, rng: &mut Rng) -> Vec<Vec<f64>> {
+    let scale = 0.1 / (factors as f64).sqrt();
+    (0..rows).map(|_| (0..factors).map(|_| rng.normal() * scale).collect()).collect()
+}
+
+//Y^T Y for the fixed side, shared by every row solved in this half step
+fn gram(fixed: &[Vec<f64>], factors: usize) -> Vec<Vec<f64>> {
+    let mut yty = vec![vec![0.0; factors]; factors];
+    for y in fixed {
+        for a in 0..factors {
+            for b in 0..factors {
+                yty[a][b] += y[a] * y[b];
+            }
+        }
+    }
+    yty
+}
+
+//re-solves every row of `solving` with the other side held fixed
+//for a row u with listens r: x_u = (Y^T Y + Y^T (C_u - I) Y + lambda I)^-1 Y^T C_u p_u
+//where C_u has 1 + alpha * r on the diagonal and p_u is 1 for every song the user played
+fn solve_side(solving: &mut [Vec<f64>], fixed: &[Vec<f64>], rows: &[Vec<(usize, f64)>], config: &AlsConfig) {
+    let factors = config.factors;
+    let yty = gram(fixed, factors);
+
+    for (x, row) in solving.iter_mut().zip(rows) {
+        let mut a = yty.clone();
+        let mut b = vec![0.0; factors];
+        for &(other, plays) in row {
+            let confidence = 1.0 + config.alpha * plays;
+            let y = &fixed[other];
+            for i in 0..factors {
+                b[i] += confidence * y[i];
+                for j in 0..factors {
+                    a[i][j] += (confidence - 1.0) * y[i] * y[j];
+                }
+            }
+        }
+        for (i, a_row) in a.iter_mut().enumerate() {
+            a_row[i] += config.regularization;
+        }
+        if let Some(solution) = solve_spd(&a, &b) {
+            *x = solution;
+        }
+    }
+}
+
+//function to train an ALS model on the user x song listen_count matrix
+//runs on the CPU one row at a time in a fixed order, so the same data, config and seed always give the same model
+pub fn train_als(interactions: &Interactions, config: &AlsConfig) -> FactorModel {
+    let mut rng = Rng::new(config.seed);
+    let mut user_factors = random_factors(interactions.num_users(), config.factors, &mut rng);
+    let mut item_factors = random_factors(interactions.num_songs(), config.factors, &mut rng);
+
+    for _ in 0..config.iterations {
+        solve_side(&mut user_factors, &item_factors, &interactions.by_user, config);
+        solve_side(&mut item_factors, &user_factors, &interactions.by_song, config);
+    }
+
+    FactorModel { user_factors, item_factors }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::MSD;
+    use crate::exclusions::Exclusions;
+    use crate::scoring::Ranker;
+    use crate::test_data::record;
+
+    //two groups of users with their own songs, plus user3 who only heard part of group A's songs
+    fn fake_data() -> Vec<MSD> {
+        let mut data = Vec::new();
+        for user in ["user1", "user2"] {
+            for song in ["A1", "A2", "A3"] {
+                data.push(record(user, song, "artistA", song, 5));
+            }
+        }
+        for user in ["user4", "user5", "user6"] {
+            for song in ["B1", "B2", "B3"] {
+                data.push(record(user, song, "artistB", song, 5));
+            }
+        }
+        data.push(record("user3", "A1", "artistA", "A1", 5));
+        data.push(record("user3", "A2", "artistA", "A2", 5));
+        data
+    }
+
+    #[test]
+    fn test_als_recommend() {
+        let interactions = Interactions::new(&fake_data());
+        let config = AlsConfig { factors: 4, iterations: 15, ..AlsConfig::default() };
+        let model = train_als(&interactions, &config);
+
+        //user3 is missing A3 from their group, so it should be the first recommendation
+        let recommended = model.recommend(&interactions, "user3", &Exclusions::new(), &Ranker::default(), 1);
+        assert_eq!(recommended[0].0, "A3");
+        //songs from the same group are the most similar
+        let similar = model.similar_songs(&interactions, "B1", &Exclusions::new(), &Ranker::default(), 2);
+        assert!(similar.iter().all(|(song, _)| song.starts_with('B')));
+        //an excluded song never comes back from either query
+        let no_a3 = Exclusions::new().with_songs(["A3"]);
+        assert!(model.recommend(&interactions, "user3", &no_a3, &Ranker::default(), 10).iter().all(|(song, _)| song != "A3"));
+        assert!(model.similar_songs(&interactions, "A1", &no_a3, &Ranker::default(), 10).iter().all(|(song, _)| song != "A3"));
+    }
+
+    #[test]
+    fn test_als_same_seed_same_model() {
+        let interactions = Interactions::new(&fake_data());
+        let config = AlsConfig { factors: 4, iterations: 3, ..AlsConfig::default() };
+        let first = train_als(&interactions, &config);
+        let second = train_als(&interactions, &config);
+        assert_eq!(first.user_factors, second.user_factors);
+        assert_eq!(first.item_factors, second.item_factors);
+    }
+}
@@ -0,0 +1,152 @@
+use crate::csv_reader::MSD;
+use crate::exclusions::Exclusions;
+use crate::scoring::{co_listen_counts, top_scores, Ranker};
+use std::collections::{HashMap, HashSet};
+
+//function to turn an artist name or artist_id into the artist_id used in the data
+//an exact artist_id match wins, otherwise the name is matched ignoring case
+pub fn find_artist_id(artist: &str, data: &[MSD]) -> Option<String> {
+    if data.iter().any(|record| record.artist_id == artist) {
+        return Some(artist.to_string());
+    }
+    data.iter()
+        .find(|record| record.artist_name.eq_ignore_ascii_case(artist))
+        .map(|record| record.artist_id.clone())
+}
+
+//function to map every artist_id to its name (for printing results)
+pub fn artist_names(data: &[MSD]) -> HashMap<String, String> {
+    let mut names = HashMap::new();
+    for record in data {
+        names.entry(record.artist_id.clone()).or_insert_with(|| record.artist_name.clone()); //first name seen is kept
+    }
+    names
+}
+
+//function to map every song_id to its artist_id (for re-rankers that look at artists)
+pub fn song_artists(data: &[MSD]) -> HashMap<String, String> {
+    data.iter().map(|record| (record.song_id.clone(), record.artist_id.clone())).collect()
+}
+
+//function to find users who have listened to any song by the artist (same idea as songs_to_users)
+pub fn artists_to_users(artist_id: &str, data: &[MSD]) -> HashSet<String> {
+    let mut user_ids_set = HashSet::new();
+    for record in data {
+        if record.artist_id == artist_id {
+            user_ids_set.insert(record.user_id.clone());
+        }
+    }
+    user_ids_set
+}
+
+//function to find every artist each user listens to (same idea as users_to_songs)
+pub fn users_to_artists(users: &HashSet<String>, data: &[MSD]) -> HashMap<String, HashSet<String>> {
+    let mut user_artists_hm: HashMap<String, HashSet<String>> = HashMap::new(); //user id -> artist ids
+    for record in data {
+        if users.contains(&record.user_id) {
+            user_artists_hm.entry(record.user_id.clone()).or_default().insert(record.artist_id.clone());
+        }
+    }
+    user_artists_hm
+}
+
+//function to recommend artists whose listeners overlap most with the input artist's listeners
+//returns up to k (artist_id, number of shared listeners) pairs, best first
+pub fn similar_artists(artist_id: &str, data: &[MSD], k: usize) -> Vec<(String, usize)> {
+    let users = artists_to_users(artist_id, data);
+    let user_artists_hm = users_to_artists(&users, data);
+    let exclusions = Exclusions::new().with_artists([artist_id], data); //the seed artist is not its own neighbour
+    let artist_score = co_listen_counts(&user_artists_hm, |artist| exclusions.excludes_artist(artist)); //same counting as most_popular_song, but over artists
+    top_scores(artist_score, &Ranker::default(), k)
+}
+
+//function to recommend songs by other artists from everyone who listens to the seed artist
+//with weight_by_share each listener counts by how much of their listening is the seed artist (a fan who plays
+//nothing else counts 1.0, a casual listener counts less), otherwise every listener counts 1.0
+//excluded songs are left out. Returns up to k (song_id, score) pairs, best first
+pub fn recommend_from_artist(artist_id: &str, data: &[MSD], weight_by_share: bool, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+    let mut artist_plays: HashMap<&str, u64> = HashMap::new(); //user -> plays of the seed artist
+    let mut total_plays: HashMap<&str, u64> = HashMap::new(); //user -> plays of everything
+    for record in data {
+        *total_plays.entry(record.user_id.as_str()).or_insert(0) += record.plays();
+        if record.artist_id == artist_id {
+            *artist_plays.entry(record.user_id.as_str()).or_insert(0) += record.plays();
+        }
+    }
+
+    //weight for each listener of the seed artist
+    let mut user_weight: HashMap<&str, f64> = HashMap::new();
+    for (user, plays) in &artist_plays {
+        let weight = if weight_by_share && total_plays[user] > 0 {
+            *plays as f64 / total_plays[user] as f64
+        } else {
+            1.0
+        };
+        user_weight.insert(user, weight);
+    }
+
+    let mut song_score: HashMap<String, f64> = HashMap::new();
+    for record in data {
+        if record.artist_id == artist_id || exclusions.excludes_song(&record.song_id) {
+            continue; //only songs by other artists are recommended
+        }
+        if let Some(weight) = user_weight.get(record.user_id.as_str()) {
+            *song_score.entry(record.song_id.clone()).or_insert(0.0) += weight;
+        }
+    }
+    top_scores(song_score, ranker, k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::record;
+
+    fn fake_data() -> Vec<MSD> {
+        vec![
+            record("user1", "S1", "A1", "Song 1", 1),
+            record("user1", "S2", "A2", "Song 2", 1),
+            record("user2", "S1", "A1", "Song 1", 1),
+            record("user2", "S3", "A2", "Song 3", 1),
+            record("user2", "S4", "A3", "Song 4", 1),
+            record("user3", "S4", "A3", "Song 4", 1),
+        ]
+    }
+
+    #[test]
+    fn test_recommend_from_artist() {
+        let mut data = fake_data();
+        data.push(record("user1", "S1", "A1", "Song 1", 8)); //user1 is a big A1 fan
+        data.push(record("user2", "S5", "A3", "Song 5", 10)); //user2 mostly plays A3
+
+        //unweighted: S1 is by the seed artist so it is never recommended, the rest count one per listener
+        let plain = recommend_from_artist("A1", &data, false, &Exclusions::new(), &Ranker::default(), 10);
+        assert!(plain.iter().all(|(song, _)| song != "S1"));
+        assert_eq!(plain[0].1, 1.0);
+
+        //weighted: user1 spends 9 of 10 plays on A1 so their song S2 ranks first
+        let weighted = recommend_from_artist("A1", &data, true, &Exclusions::new(), &Ranker::default(), 10);
+        assert_eq!(weighted[0].0, "S2");
+        assert!((weighted[0].1 - 0.9).abs() < 1e-9);
+
+        //excluding A2 drops both of its songs
+        let no_a2 = recommend_from_artist("A1", &data, true, &Exclusions::new().with_artists(["A2"], &data), &Ranker::default(), 10);
+        assert!(!no_a2.is_empty() && no_a2.iter().all(|(song, _)| song != "S2" && song != "S3"));
+    }
+
+    #[test]
+    fn test_find_artist_id() {
+        let data = fake_data();
+        assert_eq!(find_artist_id("A2", &data), Some("A2".to_string()));
+        assert_eq!(find_artist_id("a3", &data), Some("A3".to_string())); //names match ignoring case
+        assert_eq!(find_artist_id("nobody", &data), None);
+    }
+
+    #[test]
+    fn test_similar_artists() {
+        let data = fake_data();
+        let similar = similar_artists("A1", &data, 5);
+        //both A1 listeners play A2 (two different songs still count as one listener each), one plays A3
+        assert_eq!(similar, vec![("A2".to_string(), 2), ("A3".to_string(), 1)]);
+    }
+}
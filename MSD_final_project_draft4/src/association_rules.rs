@@ -0,0 +1,271 @@
+use crate::exclusions::Exclusions;
+use crate::scoring::{top_scores, Ranker};
+use csv::Writer;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+//thresholds for the Apriori miner
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuleConfig {
+    pub min_support: f64,    //a song set has to be in at least this share of baskets to be frequent
+    pub min_confidence: f64, //P(Y | X) a rule X => Y needs
+    pub min_lift: f64,       //confidence / P(Y), above 1 means X makes Y more likely than usual
+    pub max_set_size: usize, //biggest song set counted (each extra size is another pass over the baskets), at most MAX_SET_SIZE
+}
+
+//biggest max_set_size mine_rules uses, bigger values are lowered to it
+//rules split a set with a u64 bit mask (one bit per song), so a set can have at most 63 songs
+pub const MAX_SET_SIZE: usize = 63;
+
+impl Default for RuleConfig {
+    fn default() -> RuleConfig {
+        RuleConfig { min_support: 0.01, min_confidence: 0.2, min_lift: 1.0, max_set_size: 3 }
+    }
+}
+
+//a set of songs that is played together often
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrequentSet {
+    pub songs: Vec<String>, //sorted
+    pub count: usize,       //baskets with every song in the set
+    pub support: f64,       //count / number of baskets
+}
+
+//"people who played the antecedent also played the consequent"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rule {
+    pub antecedent: Vec<String>, //X, sorted
+    pub consequent: Vec<String>, //Y, sorted
+    pub support: f64,            //share of baskets with X and Y
+    pub confidence: f64,         //P(Y | X)
+    pub lift: f64,               //P(Y | X) / P(Y)
+}
+
+//the frequent song sets and the rules found in them
+pub struct AssociationRules {
+    pub sets: Vec<FrequentSet>, //by size, then most frequent first
+    pub rules: Vec<Rule>,       //highest confidence first
+}
+
+//function to count every candidate set in the baskets (baskets hold item numbers, sorted)
+//single songs are counted straight from the baskets, bigger sets are checked one by one against each basket
+fn count_sets(baskets: &[Vec<usize>], candidates: Vec<Vec<usize>>) -> HashMap<Vec<usize>, usize> {
+    if candidates.iter().all(|set| set.len() == 1) {
+        let mut counts: HashMap<Vec<usize>, usize> = HashMap::new();
+        for item in baskets.iter().flatten() {
+            *counts.entry(vec![*item]).or_insert(0) += 1;
+        }
+        return counts;
+    }
+    let mut counts: HashMap<Vec<usize>, usize> = candidates.into_iter().map(|set| (set, 0)).collect();
+    for basket in baskets {
+        for (set, count) in counts.iter_mut() {
+            if set.iter().all(|item| basket.binary_search(item).is_ok()) {
+                *count += 1;
+            }
+        }
+    }
+    counts
+}
+
+//function to split a set into X (the songs whose bit is set in mask, bit j is the j-th song) and Y (the rest)
+fn split(set: &[usize], mask: u64) -> (Vec<usize>, Vec<usize>) {
+    let in_antecedent = |j: usize| mask & (1u64 << j) != 0;
+    let antecedent: Vec<usize> = set.iter().enumerate().filter(|(j, _)| in_antecedent(*j)).map(|(_, item)| *item).collect();
+    let consequent: Vec<usize> = set.iter().enumerate().filter(|(j, _)| !in_antecedent(*j)).map(|(_, item)| *item).collect();
+    (antecedent, consequent)
+}
+
+//function to mine frequent song sets and association rules with Apriori
+//baskets is user -> songs, like users_to_songs or ListenGraph::user_songs; the songs can be titles or song_ids
+pub fn mine_rules(baskets: &HashMap<String, HashSet<String>>, config: &RuleConfig) -> AssociationRules {
+    let total = baskets.len();
+    if total == 0 {
+        return AssociationRules { sets: Vec::new(), rules: Vec::new() };
+    }
+    let min_count = ((config.min_support * total as f64).ceil() as usize).max(1);
+    let max_set_size = config.max_set_size.min(MAX_SET_SIZE);
+
+    //number the songs in sorted order, so a sorted Vec<usize> is also a sorted list of songs
+    let mut songs: Vec<&String> = baskets.values().flatten().collect::<HashSet<&String>>().into_iter().collect();
+    songs.sort();
+    let song_index: HashMap<&String, usize> = songs.iter().enumerate().map(|(i, song)| (*song, i)).collect();
+    let numbered: Vec<Vec<usize>> = baskets
+        .values()
+        .map(|basket| {
+            let mut items: Vec<usize> = basket.iter().map(|song| song_index[song]).collect();
+            items.sort();
+            items
+        })
+        .collect();
+
+    //level 1 is every single song, each later level joins two frequent sets that only differ in their last song
+    let mut frequent: HashMap<Vec<usize>, usize> = HashMap::new();
+    let mut level: Vec<Vec<usize>> = (0..songs.len()).map(|item| vec![item]).collect();
+    for size in 1..=max_set_size {
+        if level.is_empty() {
+            break;
+        }
+        let mut found: Vec<(Vec<usize>, usize)> = count_sets(&numbered, level).into_iter().filter(|(_, count)| *count >= min_count).collect();
+        found.sort();
+        frequent.extend(found.iter().cloned());
+
+        let mut next: Vec<Vec<usize>> = Vec::new();
+        if size < max_set_size {
+            for (i, (a, _)) in found.iter().enumerate() {
+                for (b, _) in &found[i + 1..] {
+                    if a[..size - 1] != b[..size - 1] {
+                        break; //found is sorted, so no later set shares a's prefix either
+                    }
+                    let mut joined = a.clone();
+                    joined.push(b[size - 1]);
+                    //every subset of a frequent set is frequent, so drop candidates with an infrequent subset
+                    let all_subsets_frequent = (0..joined.len()).all(|skip| {
+                        let subset: Vec<usize> = joined.iter().enumerate().filter(|(j, _)| *j != skip).map(|(_, item)| *item).collect();
+                        frequent.contains_key(&subset)
+                    });
+                    if all_subsets_frequent {
+                        next.push(joined);
+                    }
+                }
+            }
+        }
+        level = next;
+    }
+
+    let names = |items: &[usize]| items.iter().map(|&item| songs[item].clone()).collect::<Vec<String>>();
+    let support = |count: usize| count as f64 / total as f64;
+
+    //rules: split every frequent set of 2 or more songs into X => Y in every way
+    let mut rules: Vec<Rule> = Vec::new();
+    for (set, &count) in &frequent {
+        if set.len() < 2 {
+            continue;
+        }
+        for mask in 1..(1u64 << set.len()) - 1 {
+            let (antecedent, consequent) = split(set, mask);
+            let confidence = count as f64 / frequent[&antecedent] as f64;
+            let lift = confidence / support(frequent[&consequent]);
+            if confidence >= config.min_confidence && lift >= config.min_lift {
+                rules.push(Rule { antecedent: names(&antecedent), consequent: names(&consequent), support: support(count), confidence, lift });
+            }
+        }
+    }
+    rules.sort_by(|a, b| {
+        b.confidence
+            .total_cmp(&a.confidence)
+            .then_with(|| b.lift.total_cmp(&a.lift))
+            .then_with(|| a.antecedent.cmp(&b.antecedent))
+            .then_with(|| a.consequent.cmp(&b.consequent))
+    });
+
+    let mut sets: Vec<FrequentSet> = frequent.into_iter().map(|(set, count)| FrequentSet { songs: names(&set), count, support: support(count) }).collect();
+    sets.sort_by(|a, b| a.songs.len().cmp(&b.songs.len()).then_with(|| b.count.cmp(&a.count)).then_with(|| a.songs.cmp(&b.songs)));
+    AssociationRules { sets, rules }
+}
+
+impl AssociationRules {
+    //function to answer "if you played these songs, play this one"
+    //uses every rule whose antecedent was all played; each new song gets the best confidence of a rule that leads to it
+    //the rules have to be mined from song_id baskets for the exclusions to match
+    pub fn recommend(&self, played: &[String], exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+        let played: HashSet<&String> = played.iter().collect();
+        let mut song_score: HashMap<String, f64> = HashMap::new();
+        for rule in &self.rules {
+            if rule.antecedent.iter().all(|song| played.contains(song)) {
+                for song in rule.consequent.iter().filter(|song| !played.contains(song) && !exclusions.excludes_song(song)) {
+                    let best = song_score.entry(song.clone()).or_insert(0.0);
+                    *best = best.max(rule.confidence);
+                }
+            }
+        }
+        top_scores(song_score, ranker, k)
+    }
+
+    //function to save the rules as csv, sets are written as songs joined by " | "
+    pub fn save_rules(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = Writer::from_path(path)?;
+        writer.write_record(["antecedent", "consequent", "support", "confidence", "lift"])?;
+        for rule in &self.rules {
+            writer.write_record([rule.antecedent.join(" | "), rule.consequent.join(" | "), rule.support.to_string(), rule.confidence.to_string(), rule.lift.to_string()])?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //A and B are nearly always played together, C goes with A and B, D is on its own
+    fn fake_baskets() -> HashMap<String, HashSet<String>> {
+        let baskets = [vec!["A", "B", "C"], vec!["A", "B", "C"], vec!["A", "B"], vec!["A", "D"], vec!["D"]];
+        baskets
+            .iter()
+            .enumerate()
+            .map(|(user, songs)| (format!("user{}", user), songs.iter().map(|song| song.to_string()).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_frequent_sets() {
+        let config = RuleConfig { min_support: 0.4, min_confidence: 0.0, min_lift: 0.0, max_set_size: 3 };
+        let mined = mine_rules(&fake_baskets(), &config);
+        let sets: Vec<(Vec<String>, usize)> = mined.sets.iter().map(|set| (set.songs.clone(), set.count)).collect();
+        //2 of 5 baskets is the minimum, so every set with A, B and C counts but D with A does not
+        assert!(sets.contains(&(vec!["A".to_string()], 4)));
+        assert!(sets.contains(&(vec!["A".to_string(), "B".to_string()], 3)));
+        assert!(sets.contains(&(vec!["A".to_string(), "B".to_string(), "C".to_string()], 2)));
+        assert!(!sets.iter().any(|(songs, _)| songs == &vec!["A".to_string(), "D".to_string()]));
+        assert_eq!(mined.sets[0].songs, vec!["A".to_string()]); //singles first, most frequent first
+    }
+
+    #[test]
+    fn test_rules() {
+        let config = RuleConfig { min_support: 0.4, min_confidence: 0.9, min_lift: 1.0, max_set_size: 3 };
+        let mined = mine_rules(&fake_baskets(), &config);
+        //{B} => {A}: B is in 3 baskets and A is in all 3, lift = 1 / (4 / 5)
+        let rule = mined.rules.iter().find(|rule| rule.antecedent == vec!["B".to_string()] && rule.consequent == vec!["A".to_string()]).unwrap();
+        assert_eq!(rule.confidence, 1.0);
+        assert!((rule.lift - 1.25).abs() < 1e-12);
+        assert!((rule.support - 0.6).abs() < 1e-12);
+        //{A} => {B} is only 3 / 4 confident, under the threshold
+        assert!(!mined.rules.iter().any(|rule| rule.antecedent == vec!["A".to_string()] && rule.consequent == vec!["B".to_string()]));
+
+        //if you played A and C, play B
+        let played = vec!["A".to_string(), "C".to_string()];
+        assert_eq!(mined.recommend(&played, &Exclusions::new(), &Ranker::default(), 3), vec![("B".to_string(), 1.0)]);
+        assert!(mined.recommend(&played, &Exclusions::new().with_songs(["B"]), &Ranker::default(), 3).is_empty());
+    }
+
+    #[test]
+    fn test_max_set_size_boundary() {
+        //sizes past 32 (where a u32 mask would overflow) and past MAX_SET_SIZE give the same result as 3 here
+        let config = RuleConfig { min_support: 0.4, min_confidence: 0.0, min_lift: 0.0, max_set_size: 3 };
+        let expected = mine_rules(&fake_baskets(), &config);
+        for max_set_size in [32, 33, MAX_SET_SIZE, 64, usize::MAX] {
+            let mined = mine_rules(&fake_baskets(), &RuleConfig { max_set_size, ..config });
+            assert_eq!(mined.sets, expected.sets);
+            assert_eq!(mined.rules, expected.rules);
+        }
+
+        //a set with more than 32 songs can still be split on its high bits
+        let set: Vec<usize> = (0..MAX_SET_SIZE).collect();
+        let (antecedent, consequent) = split(&set, 1 | (1 << 40) | (1 << 62));
+        assert_eq!(antecedent, vec![0, 40, 62]);
+        assert_eq!(consequent.len(), MAX_SET_SIZE - 3);
+    }
+
+    #[test]
+    fn test_save_rules() {
+        let mined = mine_rules(&fake_baskets(), &RuleConfig { min_support: 0.4, min_confidence: 0.9, min_lift: 1.0, max_set_size: 3 });
+        let path = std::env::temp_dir().join("association_rules_test.csv");
+        mined.save_rules(path.to_str().unwrap()).unwrap();
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(saved.starts_with("antecedent,consequent,support,confidence,lift"));
+        assert_eq!(saved.lines().count(), mined.rules.len() + 1);
+        assert!(saved.contains("A | C,B,"));
+    }
+}
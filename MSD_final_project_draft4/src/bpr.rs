@@ -0,0 +1,198 @@
+use crate::factors::FactorModel;
+use crate::interactions::Interactions;
+use crate::linalg::dot;
+use crate::rng::Rng;
+
+//how the "unlistened" song in each (user, listened song, unlistened song) triple is picked
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NegativeSampling {
+    Uniform,         //every song equally likely
+    Popularity(f64), //songs picked in proportion to listeners^exponent, so popular songs the user skipped are seen more
+}
+
+//settings for BPR-MF (Rendle et al., "BPR: Bayesian Personalized Ranking from Implicit Feedback")
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BprConfig {
+    pub factors: usize,
+    pub learning_rate: f64,
+    pub regularization: f64,
+    pub epochs: usize,
+    pub negative_sampling: NegativeSampling,
+    pub auc_negatives: usize, //unlistened songs compared against each held-out song when measuring AUC
+    pub seed: u64,
+}
+
+impl Default for BprConfig {
+    fn default() -> BprConfig {
+        BprConfig {
+            factors: 32,
+            learning_rate: 0.05,
+            regularization: 0.01,
+            epochs: 20,
+            negative_sampling: NegativeSampling::Uniform,
+            auc_negatives: 100,
+            seed: 42,
+        }
+    }
+}
+
+//picks negative songs with the chosen strategy
+struct NegativeSampler {
+    cumulative: Vec<f64>, //running total of song weights (empty for uniform sampling)
+}
+
+impl NegativeSampler {
+    fn new(interactions: &Interactions, strategy: NegativeSampling) -> NegativeSampler {
+        let mut cumulative = Vec::new();
+        if let NegativeSampling::Popularity(exponent) = strategy {
+            let mut total = 0.0;
+            for listeners in &interactions.by_song {
+                total += (listeners.len() as f64).powf(exponent);
+                cumulative.push(total);
+            }
+        }
+        NegativeSampler { cumulative }
+    }
+
+    fn sample(&self, num_songs: usize, rng: &mut Rng) -> usize {
+        match self.cumulative.last() {
+            None => rng.below(num_songs),
+            Some(total) => {
+                let target = rng.next_f64() * total;
+                self.cumulative.partition_point(|&running| running <= target).min(num_songs - 1)
+            }
+        }
+    }
+}
+
+type Listens = Vec<(usize, usize)>; //(user row, song column) pairs
+
+//holds out one random listen per user (users with at least 2 listens) for validation
+//returns (training listens, held-out listens)
+fn split_holdout(interactions: &Interactions, rng: &mut Rng) -> (Listens, Listens) {
+    let mut train = Vec::new();
+    let mut holdout = Vec::new();
+    for (user, songs) in interactions.by_user.iter().enumerate() {
+        let held = if songs.len() >= 2 { Some(rng.below(songs.len())) } else { None };
+        for (position, &(song, _)) in songs.iter().enumerate() {
+            if Some(position) == held {
+                holdout.push((user, song));
+            } else {
+                train.push((user, song));
+            }
+        }
+    }
+    (train, holdout)
+}
+
+//AUC on the held-out listens: how often the held-out song outscores a song the user never played
+fn validation_auc(model: &FactorModel, interactions: &Interactions, holdout: &[(usize, usize)], negatives: usize, rng: &mut Rng) -> f64 {
+    let mut correct = 0.0;
+    let mut compared = 0.0;
+    for &(user, song) in holdout {
+        let positive = model.score(user, song);
+        for _ in 0..negatives {
+            let other = rng.below(interactions.num_songs());
+            if interactions.has_listened(user, other) {
+                continue;
+            }
+            let negative = model.score(user, other);
+            if positive > negative {
+                correct += 1.0;
+            } else if positive == negative {
+                correct += 0.5; //ties count half
+            }
+            compared += 1.0;
+        }
+    }
+    if compared == 0.0 { 0.5 } else { correct / compared }
+}
+
+//function to train BPR-MF with SGD on sampled (user, listened song, unlistened song) triples
+//one epoch takes as many steps as there are training listens. Returns the model and the validation AUC after each epoch.
+//the same data, config and seed always give the same model and AUCs
+pub fn train_bpr(interactions: &Interactions, config: &BprConfig) -> (FactorModel, Vec<f64>) {
+    let mut rng = Rng::new(config.seed);
+    let scale = 0.1 / (config.factors as f64).sqrt();
+    let mut user_factors: Vec<Vec<f64>> = (0..interactions.num_users()).map(|_| (0..config.factors).map(|_| rng.normal() * scale).collect()).collect();
+    let mut item_factors: Vec<Vec<f64>> = (0..interactions.num_songs()).map(|_| (0..config.factors).map(|_| rng.normal() * scale).collect()).collect();
+
+    let (train, holdout) = split_holdout(interactions, &mut rng);
+    let sampler = NegativeSampler::new(interactions, config.negative_sampling);
+    let mut aucs = Vec::new();
+    if train.is_empty() {
+        return (FactorModel { user_factors, item_factors }, aucs);
+    }
+
+    for _ in 0..config.epochs {
+        for _ in 0..train.len() {
+            let (user, positive) = train[rng.below(train.len())];
+            let negative = sampler.sample(interactions.num_songs(), &mut rng);
+            if interactions.has_listened(user, negative) {
+                continue; //the user played it, so it is not a negative (held-out listens are skipped too)
+            }
+
+            let x_uij = dot(&user_factors[user], &item_factors[positive]) - dot(&user_factors[user], &item_factors[negative]);
+            let sigmoid = 1.0 / (1.0 + x_uij.exp()); //gradient of ln(sigmoid(x_uij))
+            for f in 0..config.factors {
+                let w_u = user_factors[user][f];
+                let h_i = item_factors[positive][f];
+                let h_j = item_factors[negative][f];
+                user_factors[user][f] += config.learning_rate * (sigmoid * (h_i - h_j) - config.regularization * w_u);
+                item_factors[positive][f] += config.learning_rate * (sigmoid * w_u - config.regularization * h_i);
+                item_factors[negative][f] += config.learning_rate * (-sigmoid * w_u - config.regularization * h_j);
+            }
+        }
+
+        let model = FactorModel { user_factors: user_factors.clone(), item_factors: item_factors.clone() };
+        aucs.push(validation_auc(&model, interactions, &holdout, config.auc_negatives, &mut rng));
+    }
+
+    (FactorModel { user_factors, item_factors }, aucs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::MSD;
+    use crate::exclusions::Exclusions;
+    use crate::scoring::Ranker;
+    use crate::test_data::record;
+
+    //two taste groups of 6 users, each with their own 6 songs
+    fn fake_data() -> Vec<MSD> {
+        let mut data = Vec::new();
+        for group in ["A", "B"] {
+            for user in 0..6 {
+                for song in 0..6 {
+                    let song_id = format!("{}{}", group, song);
+                    data.push(record(&format!("user{}{}", group, user), &song_id, group, &song_id, 1));
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn test_bpr_learns() {
+        let interactions = Interactions::new(&fake_data());
+        let config = BprConfig { factors: 4, epochs: 30, ..BprConfig::default() };
+        let (model, aucs) = train_bpr(&interactions, &config);
+
+        assert_eq!(aucs.len(), 30);
+        //held-out songs are from the user's own group, so they should beat the other group's songs
+        assert!(*aucs.last().unwrap() > 0.9, "{:?}", aucs);
+        let similar = model.similar_songs(&interactions, "A0", &Exclusions::new(), &Ranker::default(), 3);
+        assert!(similar.iter().all(|(song, _)| song.starts_with('A')));
+    }
+
+    #[test]
+    fn test_bpr_same_seed_same_model() {
+        let interactions = Interactions::new(&fake_data());
+        let config = BprConfig { factors: 4, epochs: 2, negative_sampling: NegativeSampling::Popularity(0.75), ..BprConfig::default() };
+        let (first, first_aucs) = train_bpr(&interactions, &config);
+        let (second, second_aucs) = train_bpr(&interactions, &config);
+        assert_eq!(first.item_factors, second.item_factors);
+        assert_eq!(first_aucs, second_aucs);
+    }
+}
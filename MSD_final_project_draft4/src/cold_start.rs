@@ -0,0 +1,220 @@
+use crate::artists::{find_artist_id, similar_artists};
+use crate::csv_reader::MSD;
+use crate::exclusions::Exclusions;
+use crate::listen_graph::ListenGraph;
+use crate::recommendation::Method;
+use crate::scoring::{top_scores, Ranker};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+//the places a song with no (or very few) listeners can borrow recommendations from, most specific first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FallbackStage {
+    SameArtist,       //other songs by the song's artist, most listeners first
+    ArtistNeighbours, //the most played song of each artist most like the song's artist (artists::similar_artists)
+    ArtistChart,      //the most played song of each of the most listened to artists
+    GlobalChart,      //the most listened to songs overall
+}
+
+impl FallbackStage {
+    //the method recommendations from this stage are labelled with (one flat value per stage in the JSON)
+    pub fn method(&self) -> Method {
+        match self {
+            FallbackStage::SameArtist => Method::ColdStartSameArtist,
+            FallbackStage::ArtistNeighbours => Method::ColdStartArtistNeighbours,
+            FallbackStage::ArtistChart => Method::ColdStartArtistChart,
+            FallbackStage::GlobalChart => Method::ColdStartGlobalChart,
+        }
+    }
+}
+
+//settings for the fallback chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColdStartConfig {
+    pub stages: Vec<FallbackStage>, //tried in this order
+    pub min_results: usize,         //a stage has to find at least this many songs to be used
+    pub min_listeners: usize,       //songs with fewer listeners than this are cold, so rare songs use the chain too (1 = only unknown songs)
+}
+
+impl Default for ColdStartConfig {
+    fn default() -> ColdStartConfig {
+        ColdStartConfig {
+            stages: vec![FallbackStage::SameArtist, FallbackStage::ArtistNeighbours, FallbackStage::ArtistChart, FallbackStage::GlobalChart],
+            min_results: 1,
+            min_listeners: 3,
+        }
+    }
+}
+
+impl ColdStartConfig {
+    //true if a song with this many listeners should use the fallback chain
+    pub fn is_cold(&self, listeners: usize) -> bool {
+        listeners < self.min_listeners
+    }
+}
+
+//recommendations from the first stage of the chain that found enough songs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fallback {
+    pub stage: FallbackStage,
+    pub songs: Vec<(String, f64)>, //(song_id, score) best first; the score is a listener count (shared with the artist for ArtistNeighbours)
+}
+
+//function to find each artist's most listened to song that can still be recommended: artist_id -> (listeners, song_id)
+//songs with the same number of listeners are ordered by the ranker's policy
+fn artist_top_songs<'d>(data: &'d [MSD], graph: &ListenGraph, ranker: &Ranker, skip: impl Fn(&MSD) -> bool) -> HashMap<&'d str, (usize, &'d str)> {
+    let mut artist_top: HashMap<&str, (usize, &str)> = HashMap::new();
+    for record in data.iter().filter(|record| !skip(record)) {
+        let listeners = graph.listener_count(&record.song_id);
+        let top = artist_top.entry(&record.artist_id).or_insert((listeners, &record.song_id));
+        if listeners > top.0 || (listeners == top.0 && ranker.tie_order(&record.song_id, top.1) == Ordering::Less) {
+            *top = (listeners, &record.song_id);
+        }
+    }
+    artist_top
+}
+
+//function to recommend songs for a song nobody (or hardly anybody) has listened to, see ColdStartConfig::is_cold
+//song can be a song_id or a title; artist (a name or an artist_id) is used when the song is not in the data at all.
+//Stages that need an artist are skipped when there is none, and excluded songs are never returned.
+//Returns None if no stage finds min_results songs.
+#[allow(clippy::too_many_arguments)] //the same exclusions and tie-break as every other recommender, on top of the song and its data
+pub fn cold_start(song: &str, artist: Option<&str>, data: &[MSD], graph: &ListenGraph, config: &ColdStartConfig, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Option<Fallback> {
+    let artist_id = data
+        .iter()
+        .find(|record| record.song_id == song || record.title == song)
+        .map(|record| record.artist_id.clone())
+        .or_else(|| artist.and_then(|artist| find_artist_id(artist, data)));
+    let skip = |record: &MSD| record.song_id == song || record.title == song || exclusions.excludes_song(&record.song_id); //the song itself is never recommended
+
+    for stage in &config.stages {
+        let songs = match (stage, &artist_id) {
+            (FallbackStage::SameArtist, Some(artist_id)) => {
+                let by_artist: HashMap<String, usize> = data
+                    .iter()
+                    .filter(|record| &record.artist_id == artist_id && !skip(record))
+                    .map(|record| (record.song_id.clone(), graph.listener_count(&record.song_id)))
+                    .collect();
+                top_scores(by_artist, ranker, k).into_iter().map(|(song, count)| (song, count as f64)).collect()
+            }
+            (FallbackStage::ArtistNeighbours, Some(artist_id)) => {
+                let artist_top = artist_top_songs(data, graph, ranker, skip);
+                similar_artists(artist_id, data, usize::MAX) //every neighbour, since some may have nothing left to recommend
+                    .into_iter()
+                    .filter_map(|(neighbour, shared)| artist_top.get(neighbour.as_str()).map(|&(_, song)| (song.to_string(), shared as f64)))
+                    .take(k)
+                    .collect()
+            }
+            (FallbackStage::ArtistChart, _) => {
+                let mut artist_users: HashMap<&str, HashSet<&str>> = HashMap::new();
+                for record in data.iter().filter(|record| !skip(record)) {
+                    artist_users.entry(&record.artist_id).or_default().insert(&record.user_id);
+                }
+                let artist_top = artist_top_songs(data, graph, ranker, skip);
+                let chart: HashMap<String, usize> = artist_users.iter().map(|(artist, users)| (artist_top[artist].1.to_string(), users.len())).collect(); //each artist's listeners, keyed by its biggest song
+                top_scores(chart, ranker, k).into_iter().map(|(song, count)| (song, count as f64)).collect()
+            }
+            (FallbackStage::GlobalChart, _) => {
+                let counts: HashMap<String, usize> = data.iter().filter(|record| !skip(record)).map(|record| (record.song_id.clone(), graph.listener_count(&record.song_id))).collect();
+                top_scores(counts, ranker, k).into_iter().map(|(song, count)| (song, count as f64)).collect()
+            }
+            _ => Vec::new(), //an artist stage without an artist
+        };
+        if !songs.is_empty() && songs.len() >= config.min_results {
+            return Some(Fallback { stage: *stage, songs });
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exclusions::Exclusions;
+    use crate::test_data::record;
+
+    fn fake_data() -> Vec<MSD> {
+        vec![
+            record("user1", "S1", "A1", "Song 1", 1),
+            record("user1", "S2", "A2", "Song 2", 1),
+            record("user2", "S2", "A2", "Song 2", 1),
+            record("user2", "S3", "A2", "Song 3", 1),
+            record("user3", "S3", "A2", "Song 3", 1),
+            record("user3", "S4", "A3", "Song 4", 1),
+        ]
+    }
+
+    #[test]
+    fn test_fallback_order() {
+        let data = fake_data();
+        let graph = ListenGraph::new(&data);
+        let config = ColdStartConfig::default();
+
+        //S2 has a song by the same artist
+        let fallback = cold_start("S2", None, &data, &graph, &config, &Exclusions::new(), &Ranker::default(), 5).unwrap();
+        assert_eq!(fallback.stage, FallbackStage::SameArtist);
+        assert_eq!(fallback.songs, vec![("S3".to_string(), 2.0)]);
+
+        //A1 has no other songs, so the top song of its only similar artist A2 is used (1 shared listener, S2 wins the tie with S3)
+        let fallback = cold_start("Song 1", None, &data, &graph, &config, &Exclusions::new(), &Ranker::default(), 5).unwrap();
+        assert_eq!(fallback.stage, FallbackStage::ArtistNeighbours);
+        assert_eq!(fallback.songs, vec![("S2".to_string(), 1.0)]);
+        //with S2 excluded the neighbour's next biggest song takes its place
+        let fallback = cold_start("Song 1", None, &data, &graph, &config, &Exclusions::new().with_songs(["S2"]), &Ranker::default(), 5).unwrap();
+        assert_eq!(fallback.songs, vec![("S3".to_string(), 1.0)]);
+
+        //an unknown song with no artist goes straight to the charts
+        let fallback = cold_start("New Song", None, &data, &graph, &config, &Exclusions::new(), &Ranker::default(), 1).unwrap();
+        assert_eq!(fallback.stage, FallbackStage::ArtistChart);
+        assert_eq!(fallback.songs, vec![("S2".to_string(), 3.0)]); //A2 has 3 listeners, S2 and S3 tie and S2 wins on song_id
+    }
+
+    #[test]
+    fn test_fallback_config() {
+        let data = fake_data();
+        let graph = ListenGraph::new(&data);
+        //a known artist is used for a song that is not in the data yet
+        let config = ColdStartConfig { stages: vec![FallbackStage::SameArtist], min_results: 1, min_listeners: 1 };
+        assert_eq!(cold_start("New Song", Some("A3"), &data, &graph, &config, &Exclusions::new(), &Ranker::default(), 5).unwrap().songs[0].0, "S4");
+        //min_results 3 is too many for the same artist stage, so the global chart is used
+        let config = ColdStartConfig { stages: vec![FallbackStage::SameArtist, FallbackStage::GlobalChart], min_results: 3, min_listeners: 1 };
+        let fallback = cold_start("S2", None, &data, &graph, &config, &Exclusions::new(), &Ranker::default(), 3).unwrap();
+        assert_eq!(fallback.stage, FallbackStage::GlobalChart);
+        assert_eq!(fallback.songs[0], ("S3".to_string(), 2.0));
+        assert!(cold_start("S2", None, &data, &graph, &ColdStartConfig { stages: vec![], min_results: 1, min_listeners: 1 }, &Exclusions::new(), &Ranker::default(), 3).is_none());
+    }
+
+    #[test]
+    fn test_fallback_exclusions() {
+        let data = fake_data();
+        let graph = ListenGraph::new(&data);
+        let config = ColdStartConfig::default();
+        //S3 is S2's only same-artist song, excluding it moves on to the next stage
+        let fallback = cold_start("S2", None, &data, &graph, &config, &Exclusions::new().with_songs(["S3"]), &Ranker::default(), 5).unwrap();
+        assert_ne!(fallback.stage, FallbackStage::SameArtist);
+        assert!(fallback.songs.iter().all(|(song, _)| song != "S3"));
+        //the charts skip an excluded artist's songs
+        let no_a2 = Exclusions::new().with_artists(["A2"], &data);
+        let fallback = cold_start("New Song", None, &data, &graph, &config, &no_a2, &Ranker::default(), 5).unwrap();
+        assert!(fallback.songs.iter().all(|(song, _)| song != "S2" && song != "S3"));
+    }
+
+    #[test]
+    fn test_cold_trigger_and_method() {
+        //by default a song with 1 or 2 listeners is cold as well as an unknown one
+        let config = ColdStartConfig::default();
+        assert!(config.is_cold(0) && config.is_cold(2));
+        assert!(!config.is_cold(3));
+        assert!(!ColdStartConfig { min_listeners: 1, ..ColdStartConfig::default() }.is_cold(1));
+
+        //S1 has one listener, it still gets same-artist-or-later fallbacks
+        let data = fake_data();
+        let graph = ListenGraph::new(&data);
+        assert!(config.is_cold(graph.listener_count("S1")));
+        assert!(cold_start("S1", None, &data, &graph, &config, &Exclusions::new(), &Ranker::default(), 3).is_some());
+
+        //each stage is its own flat method in the JSON
+        assert_eq!(serde_json::to_value(FallbackStage::SameArtist.method()).unwrap(), "cold_start_same_artist");
+        assert_eq!(serde_json::to_value(FallbackStage::GlobalChart.method()).unwrap(), "cold_start_global_chart");
+    }
+}
@@ -1,11 +1,12 @@
 use csv::ReaderBuilder;
 use serde::Deserialize;
-use std::collections::{HashSet, HashMap};
 use std::error::Error;
 use std::fs::File;
 
 #[derive(Debug, Deserialize)]
+#[allow(clippy::upper_case_acronyms)] //MSD is the dataset's name (Million Song Dataset)
 pub struct MSD {
+    #[allow(dead_code)] //never used, but every column has to be in the struct to deserialize a row
     pub unknown: String, //first column just contains row number 
     pub user_id: String, //a unique ID for each user connected to their listening habits
     pub song_id: String, //unique song ID specific to a song a user is listening to
@@ -15,6 +16,13 @@ pub struct MSD {
     pub artist_name: String, //artists actual name
     pub title: String, //title of the song
 }
+impl MSD {
+    //listen_count as a number (rows where it is not a number count as 0 plays)
+    pub fn plays(&self) -> u64 {
+        self.listen_count.trim().parse().unwrap_or(0)
+    }
+}
+
 //function to read csv and convert it into a dataframe 
 pub fn read_msd(file: &File) -> Result<Vec<MSD>, Box<dyn Error>> {
     let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file); //csv has column labels
@@ -28,12 +36,12 @@ pub fn read_msd(file: &File) -> Result<Vec<MSD>, Box<dyn Error>> {
                     data.push(element);
                     line_count += 1; //add line to linecount
                 }
-                Err(failed) => {
+                Err(_failed) => {
                     eprintln!("Error deserializing record");
                     continue;
                 }
             },
-            Err(failed) => {
+            Err(_failed) => {
                 eprintln!("Error reading record");
                 continue;
             }
@@ -0,0 +1,324 @@
+use crate::csv_reader::MSD;
+use std::collections::HashMap;
+
+//words that mark a title as another version of the same song (used to cut "Song - Live" down to "Song")
+//matched as whole words only, so "Delivery" is not "live" and "Demons" is not "demo"
+const VERSION_WORDS: [&str; 14] = [
+    "remaster", "remastered", "live", "version", "edit", "edited", "mix", "remix", "mono", "stereo", "demo", "acoustic", "instrumental", "unplugged",
+];
+
+//words that number the parts of a longer piece, "(Part 1)" and "(Part 2)" are different songs
+const PART_WORDS: [&str; 6] = ["part", "pt", "vol", "volume", "movement", "chapter"];
+
+//true if any whole word of the text is in the list (ignoring case and punctuation)
+fn has_word(text: &str, words: &[&str]) -> bool {
+    normalize_name(text).split(' ').any(|word| words.contains(&word))
+}
+
+//function to turn a title into a key that matches across albums and compilations
+//"Imagine (Remastered 2010)", "imagine - Live" and "IMAGINE" all become "imagine"
+//bracketed text is dropped unless it numbers a part ("Song (Part 2)" stays "song part 2")
+pub fn normalize_title(title: &str) -> String {
+    let mut kept = String::new(); //title without the brackets that are dropped
+    let mut bracket = String::new(); //text of the bracket we are in
+    let mut depth = 0; //how many brackets deep we are
+
+    for c in title.chars() {
+        match c {
+            '(' | '[' => {
+                depth += 1;
+                bracket.push(' ');
+            }
+            ')' | ']' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    if has_word(&bracket, &PART_WORDS) {
+                        kept.push_str(&bracket);
+                        kept.push(' ');
+                    }
+                    bracket.clear();
+                }
+            }
+            ')' | ']' => {}
+            _ if depth == 0 => kept.push(c),
+            _ => bracket.push(c),
+        }
+    }
+
+    //cut off a trailing " - Remastered" style suffix, but keep titles that just contain a dash
+    if let Some(pos) = kept.rfind(" - ") {
+        if has_word(&kept[pos + 3..], &VERSION_WORDS) {
+            kept.truncate(pos);
+        }
+    }
+
+    normalize_name(&kept)
+}
+
+//function to lowercase a name and keep only letters, numbers and single spaces
+pub fn normalize_name(name: &str) -> String {
+    let cleaned: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' }) //punctuation becomes a space
+        .collect();
+    cleaned.split_whitespace().collect::<Vec<&str>>().join(" ") //collapse repeated spaces
+}
+
+//finds the root of a song_id in the union-find map (with path compression so lookups stay fast)
+fn find_root(parent: &mut HashMap<String, String>, song_id: &str) -> String {
+    let mut root = song_id.to_string();
+    while let Some(next) = parent.get(&root) {
+        if *next == root {
+            break;
+        }
+        root = next.clone();
+    }
+
+    //point every song_id on the path straight at the root
+    let mut current = song_id.to_string();
+    while current != root {
+        let next = parent[&current].clone();
+        parent.insert(current, root.clone());
+        current = next;
+    }
+    root
+}
+
+//puts two song_ids in the same cluster (smaller id becomes the root so the result does not depend on input order)
+fn union(parent: &mut HashMap<String, String>, a: &str, b: &str) {
+    let root_a = find_root(parent, a);
+    let root_b = find_root(parent, b);
+    if root_a < root_b {
+        parent.insert(root_b, root_a);
+    } else if root_b < root_a {
+        parent.insert(root_a, root_b);
+    }
+}
+
+//function to cluster duplicate songs and pick one canonical song_id for each cluster
+//two song_ids are the same song if they share a track_id or have the same normalized (artist_name, title)
+pub fn canonical_song_ids(data: &[MSD]) -> HashMap<String, String> {
+    let mut parent: HashMap<String, String> = HashMap::new(); //union-find map of song_id -> parent song_id
+    let mut first_song_for_track: HashMap<String, String> = HashMap::new(); //track_id -> first song_id seen with it
+    let mut first_song_for_name: HashMap<(String, String), String> = HashMap::new(); //(artist, title) -> first song_id seen with it
+
+    for record in data {
+        parent.entry(record.song_id.clone()).or_insert_with(|| record.song_id.clone()); //every song starts in its own cluster
+
+        match first_song_for_track.get(&record.track_id) {
+            Some(song_id) => union(&mut parent, song_id, &record.song_id),
+            None => {
+                first_song_for_track.insert(record.track_id.clone(), record.song_id.clone());
+            }
+        }
+
+        let key = (normalize_name(&record.artist_name), normalize_title(&record.title));
+        if key.0.is_empty() || key.1.is_empty() {
+            continue; //titles like "(Untitled)" or "???" have no key, so they are only merged by track_id
+        }
+        match first_song_for_name.get(&key) {
+            Some(song_id) => union(&mut parent, song_id, &record.song_id),
+            None => {
+                first_song_for_name.insert(key, record.song_id.clone());
+            }
+        }
+    }
+
+    //count records per song_id so the most listened version becomes the canonical one
+    let mut record_count: HashMap<&str, usize> = HashMap::new();
+    for record in data {
+        *record_count.entry(record.song_id.as_str()).or_insert(0) += 1;
+    }
+
+    let song_ids: Vec<String> = parent.keys().cloned().collect();
+    let mut canonical_for_root: HashMap<String, String> = HashMap::new(); //cluster root -> canonical song_id
+    for song_id in &song_ids {
+        let root = find_root(&mut parent, song_id);
+        let count = record_count[song_id.as_str()];
+        let better = match canonical_for_root.get(&root) {
+            Some(current) => {
+                let current_count = record_count[current.as_str()];
+                count > current_count || (count == current_count && song_id < current) //ties go to the smaller song_id
+            }
+            None => true,
+        };
+        if better {
+            canonical_for_root.insert(root, song_id.clone());
+        }
+    }
+
+    let mut canonical = HashMap::new();
+    for song_id in song_ids {
+        let root = find_root(&mut parent, &song_id);
+        canonical.insert(song_id, canonical_for_root[&root].clone());
+    }
+    canonical //return map of song_id -> canonical song_id
+}
+
+//function to find the title a song goes by after merge_duplicates, for titles typed in by a user
+//merged clusters take the canonical record's title, so "Imagine" may now be "Imagine (Remastered)";
+//an exact title wins, otherwise the first title with the same normalize_title key is used.
+//With an artist (name or artist_id) only that artist's songs are matched, if it has any.
+pub fn resolve_title(title: &str, artist: Option<&str>, data: &[MSD]) -> Option<String> {
+    let key = normalize_title(title);
+    let by_artist = |record: &MSD| artist.is_none_or(|artist| record.artist_id == artist || normalize_name(&record.artist_name) == normalize_name(artist));
+    let matches = |check_artist: bool| {
+        let candidates = || data.iter().filter(move |record| !check_artist || by_artist(record));
+        candidates()
+            .find(|record| record.title == title)
+            .or_else(|| if key.is_empty() { None } else { candidates().find(|record| normalize_title(&record.title) == key) })
+            .map(|record| record.title.clone())
+    };
+    matches(true).or_else(|| matches(false))
+}
+
+//function to rewrite every record to its canonical song so counts are not split across duplicates
+//records for the same user and canonical song are merged into one row with their listen_counts added together
+pub fn merge_duplicates(data: Vec<MSD>) -> Vec<MSD> {
+    let canonical = canonical_song_ids(&data);
+
+    //the canonical song's own title, artist and track are used for the whole cluster
+    let mut canonical_record: HashMap<String, (String, String, String, String)> = HashMap::new();
+    for record in &data {
+        if canonical[&record.song_id] == record.song_id && !canonical_record.contains_key(&record.song_id) {
+            canonical_record.insert(
+                record.song_id.clone(),
+                (record.track_id.clone(), record.artist_id.clone(), record.artist_name.clone(), record.title.clone()),
+            );
+        }
+    }
+
+    let mut merged: Vec<MSD> = Vec::new();
+    let mut row_for_listen: HashMap<(String, String), usize> = HashMap::new(); //(user_id, canonical song_id) -> index in merged
+
+    for mut record in data {
+        let song_id = canonical[&record.song_id].clone();
+        let key = (record.user_id.clone(), song_id.clone());
+
+        if let Some(&row) = row_for_listen.get(&key) {
+            //user already has a row for this song, so add the listens to it
+            let total = merged[row].plays() + record.plays();
+            merged[row].listen_count = total.to_string();
+            continue;
+        }
+
+        let (track_id, artist_id, artist_name, title) = canonical_record[&song_id].clone();
+        record.song_id = song_id;
+        record.track_id = track_id;
+        record.artist_id = artist_id;
+        record.artist_name = artist_name;
+        record.title = title;
+
+        row_for_listen.insert(key, merged.len());
+        merged.push(record);
+    }
+
+    merged //return deduplicated data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::record;
+
+    #[test]
+    fn test_normalize_title() {
+        assert_eq!(normalize_title("Imagine (Remastered 2010)"), "imagine");
+        assert_eq!(normalize_title("Imagine - Live"), "imagine");
+        assert_eq!(normalize_title("  IMAGINE!  "), "imagine");
+        //dash that is part of the real title is kept
+        assert_eq!(normalize_title("Love - Hate"), "love hate");
+    }
+
+    #[test]
+    fn test_normalize_title_whole_words() {
+        //version words inside other words do not cut the title
+        assert_eq!(normalize_title("X - Delivery"), "x delivery");
+        assert_eq!(normalize_title("X - Oliver"), "x oliver");
+        assert_eq!(normalize_title("X - Credits"), "x credits");
+        assert_eq!(normalize_title("X - Monologue"), "x monologue");
+        assert_eq!(normalize_title("X - Demons"), "x demons");
+        assert_eq!(normalize_title("X - Radio Edit"), "x");
+        //part and volume numbers are kept, other brackets are still dropped
+        assert_eq!(normalize_title("Suite (Part 1)"), "suite part 1");
+        assert_ne!(normalize_title("Suite (Part 1)"), normalize_title("Suite (Part 2)"));
+        assert_eq!(normalize_title("Hits [Vol. 2] (Remastered)"), "hits vol 2");
+        //titles that are only brackets or punctuation have an empty key
+        assert_eq!(normalize_title("(Untitled)"), "");
+        assert_eq!(normalize_title("???"), "");
+    }
+
+    #[test]
+    fn test_empty_titles_not_merged() {
+        let data = vec![
+            record("user1", "S1", "artist1", "(Untitled)", 1),
+            record("user2", "S2", "artist1", "???", 1),
+            record("user3", "S3", "artist1", "[Hidden Track]", 1),
+        ];
+        let canonical = canonical_song_ids(&data);
+        //nothing to match on, so every song keeps its own id
+        assert_eq!((canonical["S1"].as_str(), canonical["S2"].as_str(), canonical["S3"].as_str()), ("S1", "S2", "S3"));
+    }
+
+    #[test]
+    fn test_canonical_song_ids() {
+        let mut shared_track = record("user3", "S3", "artist2", "Other Title", 1);
+        shared_track.track_id = "TRS4".to_string(); //S3 and S4 are the same track under different song_ids
+        let data = vec![
+            record("user1", "S1", "artist1", "Imagine", 1),
+            record("user2", "S2", "artist1", "Imagine (Remastered)", 1),
+            record("user3", "S2", "artist1", "Imagine (Remastered)", 1),
+            shared_track,
+            record("user4", "S4", "artist2", "Something Else", 1),
+            record("user5", "S5", "artist2", "Imagine", 1), //same title but a different artist
+        ];
+        let canonical = canonical_song_ids(&data);
+
+        //S2 has more listeners than S1 so it is the canonical version
+        assert_eq!(canonical["S1"], "S2");
+        assert_eq!(canonical["S2"], "S2");
+        //S3 and S4 share a track_id
+        assert_eq!(canonical["S3"], canonical["S4"]);
+        assert_eq!(canonical["S5"], "S5");
+    }
+
+    #[test]
+    fn test_merge_duplicates() {
+        let data = vec![
+            record("user1", "S1", "artist1", "Imagine", 3),
+            record("user1", "S2", "artist1", "Imagine (Live)", 2), //same user, duplicate song
+            record("user2", "S2", "artist1", "Imagine (Live)", 1),
+        ];
+        let merged = merge_duplicates(data);
+
+        //user1's two rows are merged and their listens added
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].song_id, merged[1].song_id);
+        assert_eq!(merged[0].listen_count, "5");
+        //every row takes the canonical song's title
+        assert_eq!(merged[0].title, merged[1].title);
+    }
+
+    #[test]
+    fn test_resolve_title_after_merge() {
+        let data = vec![
+            record("user1", "S1", "artist1", "Imagine", 1),
+            record("user2", "S2", "artist1", "Imagine (Remastered)", 1),
+            record("user3", "S2", "artist1", "Imagine (Remastered)", 1),
+            record("user4", "S5", "artist2", "Imagine - Live", 1), //another artist's song with the same key
+        ];
+        let merged = merge_duplicates(data);
+        //"Imagine" itself is gone, it merged into the remaster
+        assert!(merged.iter().all(|record| record.title != "Imagine"));
+        assert_eq!(resolve_title("Imagine", Some("artist1"), &merged), Some("Imagine (Remastered)".to_string()));
+        let catalog = crate::recommendation::SongCatalog::new(&merged);
+        assert_eq!(catalog.song_id(&resolve_title("imagine!", Some("artist1"), &merged).unwrap()), Some(&"S2".to_string()));
+        //the artist picks between songs with the same key, an unknown artist falls back to any of them
+        assert_eq!(resolve_title("Imagine", Some("artist2"), &merged), Some("Imagine - Live".to_string()));
+        assert!(resolve_title("Imagine", Some("nobody"), &merged).is_some());
+        //an exact title is used as it is, an unknown one is None
+        assert_eq!(resolve_title("Imagine - Live", None, &merged), Some("Imagine - Live".to_string()));
+        assert_eq!(resolve_title("Yesterday", None, &merged), None);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::listen_graph::ListenGraph;
+use crate::scoring::Ranker;
+use crate::similarity::Similarity;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+//settings for the maximal marginal relevance re-ranker
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiversityConfig {
+    pub relevance: f64,        //trade-off in [0, 1]: 1 keeps the recommender's order, 0 only looks at how different songs are
+    pub max_per_artist: usize, //at most this many songs by one artist_id
+    pub similarity: Similarity, //how alike two songs are, from their listener sets
+}
+
+impl Default for DiversityConfig {
+    fn default() -> DiversityConfig {
+        DiversityConfig { relevance: 0.7, max_per_artist: 1, similarity: Similarity::Cosine }
+    }
+}
+
+//function to re-rank a recommender's output so the top k is not the same artist or the same kind of song over and over
+//songs are picked one at a time by relevance * normalized score - (1 - relevance) * similarity to the closest song already picked,
+//skipping artists that already have max_per_artist songs (songs with no known artist are never capped).
+//Scores are kept as they were, only the order and the cut change.
+pub fn diversify(ranked: &[(String, f64)], graph: &ListenGraph, song_artists: &HashMap<String, String>, config: &DiversityConfig, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+    let total_users = graph.total_users();
+    let song_similarity = |a: &str, b: &str| match (graph.song_users.get(a), graph.song_users.get(b)) {
+        (Some(a_users), Some(b_users)) => {
+            let shared = a_users.intersection(b_users).count();
+            config.similarity.score(shared, a_users.len(), b_users.len(), total_users)
+        }
+        _ => 0.0, //songs outside the graph are not like anything
+    };
+
+    //scores from different recommenders are on different scales, so bring them into [0, 1]
+    let lowest = ranked.iter().map(|(_, score)| *score).fold(f64::INFINITY, f64::min);
+    let highest = ranked.iter().map(|(_, score)| *score).fold(f64::NEG_INFINITY, f64::max);
+    let spread = if highest > lowest { highest - lowest } else { 1.0 };
+
+    let mut remaining: Vec<usize> = (0..ranked.len()).collect(); //positions in ranked, still best first
+    let mut closest: Vec<f64> = vec![0.0; ranked.len()]; //similarity of each candidate to the closest picked song
+    let mut per_artist: HashMap<&str, usize> = HashMap::new();
+    let mut picked: Vec<(String, f64)> = Vec::new();
+
+    while picked.len() < k {
+        remaining.retain(|&i| match song_artists.get(&ranked[i].0) {
+            Some(artist) => per_artist.get(artist.as_str()).copied().unwrap_or(0) < config.max_per_artist,
+            None => true, //an unknown artist is not one artist, so it has no cap
+        });
+        //the best marginal score wins, ties are ordered by the ranker's policy
+        let mut best: Option<(usize, f64)> = None;
+        for (slot, &i) in remaining.iter().enumerate() {
+            let relevance = (ranked[i].1 - lowest) / spread;
+            let marginal = config.relevance * relevance - (1.0 - config.relevance) * closest[i];
+            let wins = match best {
+                None => true,
+                Some((top_slot, top)) => marginal > top || (marginal == top && ranker.tie_order(&ranked[i].0, &ranked[remaining[top_slot]].0) == Ordering::Less),
+            };
+            if wins {
+                best = Some((slot, marginal));
+            }
+        }
+        let chosen = match best {
+            Some((slot, _)) => remaining.remove(slot),
+            None => break, //nothing left under the artist caps
+        };
+
+        let song = &ranked[chosen].0;
+        if let Some(artist) = song_artists.get(song) {
+            *per_artist.entry(artist.as_str()).or_insert(0) += 1;
+        }
+        for &i in &remaining {
+            closest[i] = closest[i].max(song_similarity(song, &ranked[i].0));
+        }
+        picked.push(ranked[chosen].clone());
+    }
+    picked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::artists::song_artists;
+    use crate::csv_reader::MSD;
+    use crate::test_data::record;
+
+    //S1 and S2 are by A1 and have the same listeners, S3 is by A2 with other listeners
+    fn fake_data() -> Vec<MSD> {
+        vec![
+            record("user1", "S1", "A1", "Song 1", 1),
+            record("user1", "S2", "A1", "Song 2", 1),
+            record("user2", "S1", "A1", "Song 1", 1),
+            record("user2", "S2", "A1", "Song 2", 1),
+            record("user3", "S3", "A2", "Song 3", 1),
+            record("user3", "S4", "A3", "Song 4", 1),
+            record("user1", "S4", "A3", "Song 4", 1),
+        ]
+    }
+
+    fn candidates() -> Vec<(String, f64)> {
+        vec![("S1".to_string(), 3.0), ("S2".to_string(), 2.9), ("S4".to_string(), 2.0), ("S3".to_string(), 1.0)]
+    }
+
+    #[test]
+    fn test_artist_cap() {
+        let data = fake_data();
+        let graph = ListenGraph::new(&data);
+        //pure relevance keeps the order apart from the artist cap dropping S2
+        let config = DiversityConfig { relevance: 1.0, max_per_artist: 1, similarity: Similarity::Cosine };
+        let songs: Vec<String> = diversify(&candidates(), &graph, &song_artists(&data), &config, &Ranker::default(), 4).into_iter().map(|(song, _)| song).collect();
+        assert_eq!(songs, vec!["S1", "S4", "S3"]);
+    }
+
+    #[test]
+    fn test_unknown_artists_not_capped() {
+        let data = fake_data();
+        let graph = ListenGraph::new(&data);
+        //none of the songs has a known artist, so the cap of 1 does not cut the list down to one song
+        let config = DiversityConfig { relevance: 1.0, max_per_artist: 1, similarity: Similarity::Cosine };
+        let songs: Vec<String> = diversify(&candidates(), &graph, &HashMap::new(), &config, &Ranker::default(), 4).into_iter().map(|(song, _)| song).collect();
+        assert_eq!(songs, vec!["S1", "S2", "S4", "S3"]);
+    }
+
+    #[test]
+    fn test_diversify() {
+        let data = fake_data();
+        let graph = ListenGraph::new(&data);
+        let artists = song_artists(&data);
+        //no artist cap: with relevance 1 the order does not change
+        let relevant = DiversityConfig { relevance: 1.0, max_per_artist: 10, similarity: Similarity::Cosine };
+        assert_eq!(diversify(&candidates(), &graph, &artists, &relevant, &Ranker::default(), 4), candidates());
+        //with more weight on diversity, S3 (no listeners in common with S1) moves up and S2 (a copy of S1) goes last
+        let diverse = DiversityConfig { relevance: 0.3, ..relevant };
+        let songs: Vec<String> = diversify(&candidates(), &graph, &artists, &diverse, &Ranker::default(), 4).into_iter().map(|(song, _)| song).collect();
+        assert_eq!(songs, vec!["S1", "S3", "S4", "S2"]);
+    }
+}
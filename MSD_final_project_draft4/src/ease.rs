@@ -0,0 +1,171 @@
+use crate::exclusions::Exclusions;
+use crate::interactions::Interactions;
+use crate::linalg::invert_spd;
+use crate::scoring::{top_scores, Ranker};
+use csv::Writer;
+use std::collections::HashMap;
+use std::error::Error;
+
+//settings for EASE (Steck, "Embarrassingly Shallow Autoencoders for Sparse Data")
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EaseConfig {
+    pub top_n: usize, //only the top_n songs by listeners are modelled, the weight matrix is top_n x top_n (and inverted once)
+    pub lambda: f64,  //ridge regularization added to the diagonal of the Gram matrix
+}
+
+impl Default for EaseConfig {
+    fn default() -> EaseConfig {
+        EaseConfig { top_n: 300, lambda: 500.0 }
+    }
+}
+
+//learned item-item weights: a user's score for song j is the sum of weights[i][j] over the songs i they played
+pub struct EaseModel {
+    pub song_ids: Vec<String>,               //model row/column -> song_id
+    pub song_index: HashMap<String, usize>,  //song_id -> model row/column
+    pub weights: Vec<Vec<f64>>,              //weights[i][j], the diagonal is 0 so a song can not recommend itself
+}
+
+//function to fit EASE on the top_n most popular songs
+//closed form: P = (X^T X + lambda I)^-1, then B[i][j] = -P[i][j] / P[j][j] and B[j][j] = 0
+//returns None if there are no songs (the Gram matrix plus lambda is always invertible for lambda > 0)
+pub fn train_ease(interactions: &Interactions, config: &EaseConfig) -> Option<EaseModel> {
+    //most listened songs first, ties by song_id so the chosen set is always the same
+    let mut columns: Vec<usize> = (0..interactions.num_songs()).collect();
+    columns.sort_by(|&a, &b| {
+        interactions.by_song[b].len().cmp(&interactions.by_song[a].len()).then_with(|| interactions.song_ids[a].cmp(&interactions.song_ids[b]))
+    });
+    columns.truncate(config.top_n);
+    if columns.is_empty() {
+        return None;
+    }
+
+    let n = columns.len();
+    let song_ids: Vec<String> = columns.iter().map(|&column| interactions.song_ids[column].clone()).collect();
+    let song_index: HashMap<String, usize> = song_ids.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect();
+    let model_index: HashMap<usize, usize> = columns.iter().enumerate().map(|(i, &column)| (column, i)).collect();
+
+    //Gram matrix G = X^T X on the binary listened / not listened matrix, plus lambda on the diagonal
+    let mut gram = vec![vec![0.0; n]; n];
+    for songs in &interactions.by_user {
+        let kept: Vec<usize> = songs.iter().filter_map(|(column, _)| model_index.get(column).copied()).collect();
+        for &i in &kept {
+            for &j in &kept {
+                gram[i][j] += 1.0;
+            }
+        }
+    }
+    for (i, row) in gram.iter_mut().enumerate() {
+        row[i] += config.lambda;
+    }
+
+    let p = invert_spd(&gram)?;
+    let mut weights = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..n {
+            if i != j {
+                weights[i][j] = -p[i][j] / p[j][j];
+            }
+        }
+    }
+
+    Some(EaseModel { song_ids, song_index, weights })
+}
+
+impl EaseModel {
+    //function to score every modelled song for a history of song_ids (songs outside the model are ignored)
+    pub fn score(&self, history: &[String]) -> Vec<f64> {
+        let mut scores = vec![0.0; self.song_ids.len()];
+        for song in history {
+            if let Some(&i) = self.song_index.get(song) {
+                for (score, weight) in scores.iter_mut().zip(&self.weights[i]) {
+                    *score += weight;
+                }
+            }
+        }
+        scores
+    }
+
+    //function to recommend the k best modelled songs for a user, leaving out songs they already heard and excluded songs
+    pub fn recommend(&self, interactions: &Interactions, user_id: &str, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+        let user = match interactions.user_index.get(user_id) {
+            Some(&user) => user,
+            None => return Vec::new(),
+        };
+        let history: Vec<String> = interactions.by_user[user].iter().map(|&(song, _)| interactions.song_ids[song].clone()).collect();
+
+        let mut song_score: HashMap<String, f64> = HashMap::new();
+        for (song_id, score) in self.song_ids.iter().zip(self.score(&history)) {
+            if !history.contains(song_id) && !exclusions.excludes_song(song_id) {
+                song_score.insert(song_id.clone(), score);
+            }
+        }
+        top_scores(song_score, ranker, k)
+    }
+
+    //function to save the learned weights as a csv of (from_song_id, to_song_id, weight), zero weights left out
+    pub fn save_weights(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = Writer::from_path(path)?;
+        writer.write_record(["from_song_id", "to_song_id", "weight"])?;
+        for (i, row) in self.weights.iter().enumerate() {
+            for (j, weight) in row.iter().enumerate() {
+                if *weight != 0.0 {
+                    writer.write_record([self.song_ids[i].as_str(), self.song_ids[j].as_str(), &weight.to_string()])?;
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::MSD;
+    use crate::test_data::record;
+
+    fn fake_data() -> Vec<MSD> {
+        let mut data = Vec::new();
+        //S1 and S2 are always played together, S3 is on its own, S4 only has one listener
+        for user in ["user1", "user2", "user3"] {
+            data.push(record(user, "S1", "A1", "Song 1", 1));
+            data.push(record(user, "S2", "A1", "Song 2", 1));
+        }
+        data.push(record("user4", "S3", "A2", "Song 3", 1));
+        data.push(record("user5", "S1", "A1", "Song 1", 1));
+        data.push(record("user5", "S3", "A2", "Song 3", 1));
+        data.push(record("user6", "S4", "A3", "Song 4", 1));
+        data
+    }
+
+    #[test]
+    fn test_train_ease() {
+        let interactions = Interactions::new(&fake_data());
+        let model = train_ease(&interactions, &EaseConfig { top_n: 3, lambda: 1.0 }).unwrap();
+
+        //S4 is the least popular so it is left out
+        assert_eq!(model.song_ids, vec!["S1", "S2", "S3"]);
+        assert_eq!(model.weights[0][0], 0.0);
+        //user5 played S1 and S3, S2 is the only modelled song left and it goes with S1
+        let recommended = model.recommend(&interactions, "user5", &Exclusions::new(), &Ranker::default(), 5);
+        assert_eq!(recommended.len(), 1);
+        assert_eq!(recommended[0].0, "S2");
+        assert!(recommended[0].1 > 0.0);
+        assert!(model.recommend(&interactions, "user5", &Exclusions::new().with_songs(["S2"]), &Ranker::default(), 5).is_empty());
+    }
+
+    #[test]
+    fn test_save_weights() {
+        let interactions = Interactions::new(&fake_data());
+        let model = train_ease(&interactions, &EaseConfig { top_n: 2, lambda: 1.0 }).unwrap();
+        let path = std::env::temp_dir().join("ease_weights_test.csv");
+        model.save_weights(path.to_str().unwrap()).unwrap();
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        //header plus the two off-diagonal weights
+        assert_eq!(saved.lines().count(), 3);
+        assert!(saved.starts_with("from_song_id,to_song_id,weight"));
+    }
+}
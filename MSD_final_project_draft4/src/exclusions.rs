@@ -0,0 +1,127 @@
+use crate::csv_reader::MSD;
+use crate::recommendation::SongCatalog;
+use std::collections::{HashMap, HashSet};
+
+//everything a recommender must not return: seed songs, blocklisted songs and artists, and songs the user already heard
+//replaces passing one exclude_input string (and "EMPTY" when there was nothing to exclude)
+//songs are kept by song_id and artists by artist_id, so a title shared by two artists or a song_id that looks like an
+//artist_id never excludes the wrong thing. The title based functions map titles through the SongCatalog.
+#[derive(Debug, Clone, Default)]
+pub struct Exclusions {
+    songs: HashSet<String>,        //excluded song_ids, given directly or from a user's history
+    artists: HashSet<String>,      //excluded artist_ids
+    artist_songs: HashSet<String>, //song_ids of every song by an excluded artist
+}
+
+impl Exclusions {
+    //excludes nothing
+    pub fn new() -> Exclusions {
+        Exclusions::default()
+    }
+
+    pub fn with_songs<I: IntoIterator<Item = S>, S: Into<String>>(mut self, song_ids: I) -> Exclusions {
+        self.songs.extend(song_ids.into_iter().map(Into::into));
+        self
+    }
+
+    //excludes songs given by title, every song_id with the title (titles that are not in the catalog are ignored)
+    pub fn with_titles<I: IntoIterator<Item = S>, S: AsRef<str>>(mut self, titles: I, catalog: &SongCatalog) -> Exclusions {
+        for title in titles {
+            self.songs.extend(catalog.song_ids(title.as_ref()).iter().cloned());
+        }
+        self
+    }
+
+    //excludes the artists and every song they have in the data
+    pub fn with_artists<I: IntoIterator<Item = S>, S: Into<String>>(mut self, artist_ids: I, data: &[MSD]) -> Exclusions {
+        self.artists.extend(artist_ids.into_iter().map(Into::into));
+        for record in data {
+            if self.artists.contains(&record.artist_id) {
+                self.artist_songs.insert(record.song_id.clone());
+            }
+        }
+        self
+    }
+
+    //excludes every song the user has already listened to
+    pub fn with_heard_by(mut self, user_id: &str, data: &[MSD]) -> Exclusions {
+        for record in data {
+            if record.user_id == user_id {
+                self.songs.insert(record.song_id.clone());
+            }
+        }
+        self
+    }
+
+    pub fn excludes_song(&self, song_id: &str) -> bool {
+        self.songs.contains(song_id) || self.artist_songs.contains(song_id)
+    }
+
+    pub fn excludes_artist(&self, artist_id: &str) -> bool {
+        self.artists.contains(artist_id)
+    }
+
+    //for the title based functions (most_popular_song): a title is excluded if any song with that title is,
+    //since counting by title cannot tell the songs apart and an excluded song must never come back under its title
+    pub fn excludes_title(&self, title: &str, catalog: &SongCatalog) -> bool {
+        catalog.song_ids(title).iter().any(|song_id| self.excludes_song(song_id))
+    }
+
+    //function to drop every excluded song from a map of song_id -> score (use before ranking so top k is still k long)
+    pub fn filter<S>(&self, scores: HashMap<String, S>) -> HashMap<String, S> {
+        scores.into_iter().filter(|(song_id, _)| !self.excludes_song(song_id)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::record;
+
+    #[test]
+    fn test_exclusions() {
+        let data = vec![
+            record("user1", "S1", "A1", "Song 1", 1),
+            record("user1", "S2", "A2", "Song 2", 1),
+            record("user2", "S3", "A3", "Intro", 1),
+            record("user2", "S4", "A3", "Song 4", 1),
+            record("user3", "S5", "A4", "Intro", 1), //same title as S3 by another artist
+            record("user3", "A3", "A4", "Odd Id", 1), //a song_id that is also an artist_id
+        ];
+        let exclusions = Exclusions::new().with_songs(["S9"]).with_artists(["A3"], &data).with_heard_by("user1", &data);
+
+        assert!(exclusions.excludes_song("S9"));
+        //user1's history
+        assert!(exclusions.excludes_song("S1") && exclusions.excludes_song("S2"));
+        //every song by A3, and A3 itself as an artist
+        assert!(exclusions.excludes_song("S3") && exclusions.excludes_song("S4") && exclusions.excludes_artist("A3"));
+        //A4's "Intro" and the song with id "A3" are not touched
+        assert!(!exclusions.excludes_song("S5") && !exclusions.excludes_song("A3"));
+        assert!(!exclusions.excludes_artist("S1"));
+        assert!(!Exclusions::new().excludes_song("S1"));
+
+        let scores: HashMap<String, usize> = [("S1".to_string(), 3), ("S5".to_string(), 1)].into_iter().collect();
+        assert_eq!(exclusions.filter(scores).len(), 1);
+    }
+
+    #[test]
+    fn test_exclude_titles() {
+        let data = vec![
+            record("user1", "S1", "A1", "Song 1", 1),
+            record("user1", "S2", "A2", "Song 2", 1),
+            record("user2", "S3", "A3", "Intro", 1),
+            record("user3", "S4", "A4", "Intro", 1), //same title by another artist
+        ];
+        let catalog = SongCatalog::new(&data);
+        let exclusions = Exclusions::new().with_titles(["Song 1", "Not In Data"], &catalog);
+        assert!(exclusions.excludes_song("S1"));
+        assert!(exclusions.excludes_title("Song 1", &catalog));
+        assert!(!exclusions.excludes_title("Song 2", &catalog));
+
+        //a shared title excludes every song with it
+        let intro = Exclusions::new().with_titles(["Intro"], &catalog);
+        assert!(intro.excludes_song("S3") && intro.excludes_song("S4"));
+        //excluding one of them by song_id still keeps the title out of title based counts
+        assert!(Exclusions::new().with_songs(["S4"]).excludes_title("Intro", &catalog));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::exclusions::Exclusions;
+use crate::listen_graph::ListenGraph;
+use crate::scoring::Ranker;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+//settings for the multi-hop recommender
+//the defaults only borrow find_more_songs' numbers (fewer than 5 listeners, top 3 songs, one extra hop, no decay);
+//the walk itself is different (song_ids, scores for every song found, no single winner), so results can differ
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExpansionConfig {
+    pub hops: usize,               //how many times to expand out from the seed (1 = only the seed's own listeners)
+    pub fan_out: usize,            //how many of each hop's best songs are used as seeds for the next hop
+    pub min_support: usize,        //songs with fewer co-listeners than this in a hop are ignored (a per-hop count, not a seed check)
+    pub decay: f64,                //scores found at hop h are multiplied by decay^(h - 1)
+    pub min_seed_listeners: usize, //seeds with at least this many listeners stop after hop 1, like find_more_songs stops
+                                   //for songs with 5 or more; usize::MAX expands every seed
+}
+
+impl Default for ExpansionConfig {
+    fn default() -> ExpansionConfig {
+        ExpansionConfig { hops: 2, fan_out: 3, min_support: 1, decay: 1.0, min_seed_listeners: 5 }
+    }
+}
+
+//one song found by the expansion
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpandedSong {
+    pub song_id: String,
+    pub score: f64,        //co-listeners in the hop it was found at, after decay
+    pub listeners: usize,  //co-listeners before decay
+    pub hop: usize,        //which hop found it (1 = direct neighbour of the seed)
+}
+
+//function to walk out from the seed song hop by hop on the user-song graph
+//each hop takes the listeners of the current frontier songs, counts their other songs, and keeps the best fan_out
+//songs as the next frontier. A song is reported at the first hop it shows up in. Excluded songs are still walked
+//through (their listeners can lead somewhere) but never reported.
+pub fn expand(seed_song_id: &str, graph: &ListenGraph, config: &ExpansionConfig, exclusions: &Exclusions, ranker: &Ranker) -> Vec<ExpandedSong> {
+    let mut seen: HashSet<String> = HashSet::new(); //seed plus every song already reported
+    seen.insert(seed_song_id.to_string());
+    let mut frontier: Vec<String> = vec![seed_song_id.to_string()];
+    let mut found: Vec<ExpandedSong> = Vec::new();
+    let mut weight = 1.0; //decay^(hop - 1)
+    //a seed with enough listeners of its own is popular enough that its direct neighbours are used as they are
+    let hops = if graph.listener_count(seed_song_id) >= config.min_seed_listeners { config.hops.min(1) } else { config.hops };
+
+    for hop in 1..=hops {
+        //everyone who listened to a frontier song
+        let mut users: HashSet<&String> = HashSet::new();
+        for song in &frontier {
+            if let Some(listeners) = graph.song_users.get(song) {
+                users.extend(listeners);
+            }
+        }
+
+        //count how many of those users played each song we have not reported yet
+        let mut counts: HashMap<&String, usize> = HashMap::new();
+        for user in users {
+            for song in &graph.user_songs[user] {
+                if !seen.contains(song) {
+                    *counts.entry(song).or_insert(0) += 1;
+                }
+            }
+        }
+
+        let mut hop_songs: Vec<(&String, usize)> = counts.into_iter().filter(|(_, count)| *count >= config.min_support).collect();
+        hop_songs.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| ranker.tie_order(a.0, b.0))); //most co-listeners first, ties by the ranker's policy
+        if hop_songs.is_empty() {
+            break; //nothing new to expand into
+        }
+
+        frontier = hop_songs.iter().take(config.fan_out).map(|(song, _)| (*song).clone()).collect();
+        for (song, count) in hop_songs {
+            seen.insert(song.clone());
+            if exclusions.excludes_song(song) {
+                continue;
+            }
+            found.push(ExpandedSong { song_id: song.clone(), score: count as f64 * weight, listeners: count, hop });
+        }
+        weight *= config.decay;
+    }
+
+    //best score first, earlier hop wins a tie, then the ranker's policy
+    found.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.hop.cmp(&b.hop))
+            .then_with(|| ranker.tie_order(&a.song_id, &b.song_id))
+    });
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::record;
+
+    //S1 -> S2 (user1) -> S3 (user2) -> S4 (user3), a chain one hop apart each
+    fn fake_graph() -> ListenGraph {
+        ListenGraph::new(&[
+            record("user1", "S1", "A1", "Song 1", 1),
+            record("user1", "S2", "A1", "Song 2", 1),
+            record("user2", "S2", "A1", "Song 2", 1),
+            record("user2", "S3", "A1", "Song 3", 1),
+            record("user3", "S3", "A1", "Song 3", 1),
+            record("user3", "S4", "A1", "Song 4", 1),
+        ])
+    }
+
+    #[test]
+    fn test_expand_hops() {
+        let graph = fake_graph();
+        let config = ExpansionConfig { hops: 3, fan_out: 1, min_support: 1, decay: 0.5, min_seed_listeners: 5 };
+        let found = expand("S1", &graph, &config, &Exclusions::new(), &Ranker::default());
+
+        let hops: Vec<(&str, usize)> = found.iter().map(|song| (song.song_id.as_str(), song.hop)).collect();
+        assert_eq!(hops, vec![("S2", 1), ("S3", 2), ("S4", 3)]);
+        //hop 3 score is decayed twice
+        assert_eq!(found[2].score, 0.25);
+        assert_eq!(found[2].listeners, 1);
+
+        //S2 is excluded, but the walk still goes through it to reach S3 and S4
+        let found = expand("S1", &graph, &config, &Exclusions::new().with_songs(["S2"]), &Ranker::default());
+        let hops: Vec<(&str, usize)> = found.iter().map(|song| (song.song_id.as_str(), song.hop)).collect();
+        assert_eq!(hops, vec![("S3", 2), ("S4", 3)]);
+    }
+
+    #[test]
+    fn test_expand_limits() {
+        let graph = fake_graph();
+        //one hop only reaches S2
+        let one_hop = expand("S1", &graph, &ExpansionConfig { hops: 1, ..ExpansionConfig::default() }, &Exclusions::new(), &Ranker::default());
+        assert_eq!(one_hop.len(), 1);
+        //nothing has two co-listeners so min_support 2 finds nothing
+        let strict = expand("S1", &graph, &ExpansionConfig { min_support: 2, ..ExpansionConfig::default() }, &Exclusions::new(), &Ranker::default());
+        assert!(strict.is_empty());
+    }
+
+    #[test]
+    fn test_expand_popular_seed() {
+        //S1 gets 5 listeners, S2 is on the chain one hop out and S3 two hops out
+        let mut data = vec![
+            record("user1", "S1", "A1", "Song 1", 1),
+            record("user1", "S2", "A1", "Song 2", 1),
+            record("user2", "S2", "A1", "Song 2", 1),
+            record("user2", "S3", "A1", "Song 3", 1),
+        ];
+        for user in ["user3", "user4", "user5", "user6"] {
+            data.push(record(user, "S1", "A1", "Song 1", 1));
+        }
+        let graph = ListenGraph::new(&data);
+        //5 listeners is popular, so only the first hop is used
+        let found = expand("S1", &graph, &ExpansionConfig::default(), &Exclusions::new(), &Ranker::default());
+        assert_eq!(found.iter().map(|song| song.song_id.as_str()).collect::<Vec<&str>>(), vec!["S2"]);
+        //raising the cutoff expands it like a rare song
+        let found = expand("S1", &graph, &ExpansionConfig { min_seed_listeners: 6, ..ExpansionConfig::default() }, &Exclusions::new(), &Ranker::default());
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[1].song_id, "S3");
+    }
+}
@@ -0,0 +1,109 @@
+use crate::listen_graph::ListenGraph;
+use crate::recommendation::{Recommendation, SongCatalog};
+use serde::Serialize;
+use std::collections::HashSet;
+
+//one song that helped produce a recommendation, with how many listeners it shares with the recommended song
+//only counts are kept, never which users they were
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Evidence {
+    pub song_id: String,
+    pub title: String,
+    pub shared_listeners: usize,
+}
+
+//the "because you listened to..." evidence for one recommendation
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Explanation {
+    pub because_of: Vec<Evidence>, //seed or history songs that share the most listeners with the recommendation, best first
+    pub shared_listeners: usize,   //listeners of the recommendation who played at least one of the seeds
+}
+
+impl Explanation {
+    //"because you listened to 'A' (12 shared listeners) and 'B' (3 shared listeners)"
+    pub fn sentence(&self) -> String {
+        let parts: Vec<String> = self.because_of.iter().map(|evidence| format!("'{}' ({} shared listeners)", evidence.title, evidence.shared_listeners)).collect();
+        match parts.len() {
+            0 => "no shared listeners with your songs".to_string(),
+            1 => format!("because you listened to {}", parts[0]),
+            _ => format!("because you listened to {} and {}", parts[..parts.len() - 1].join(", "), parts[parts.len() - 1]),
+        }
+    }
+}
+
+//function to explain a recommended song from the seed songs it was recommended for
+//works for a single seed, a playlist, or a user's whole history; keeps the max_evidence seeds with the most shared listeners
+pub fn explain(song_id: &str, seeds: &[String], graph: &ListenGraph, catalog: &SongCatalog, max_evidence: usize) -> Explanation {
+    let empty = HashSet::new();
+    let listeners = graph.song_users.get(song_id).unwrap_or(&empty);
+
+    let mut reached: HashSet<&String> = HashSet::new(); //listeners of the recommendation who played any seed
+    let mut because_of: Vec<Evidence> = Vec::new();
+    for seed in seeds {
+        if seed == song_id {
+            continue;
+        }
+        let seed_listeners = match graph.song_users.get(seed) {
+            Some(seed_listeners) => seed_listeners,
+            None => continue,
+        };
+        let shared: Vec<&String> = listeners.intersection(seed_listeners).collect();
+        if !shared.is_empty() {
+            because_of.push(Evidence { song_id: seed.clone(), title: catalog.title(seed).to_string(), shared_listeners: shared.len() });
+            reached.extend(shared);
+        }
+    }
+
+    because_of.sort_by(|a, b| b.shared_listeners.cmp(&a.shared_listeners).then_with(|| a.song_id.cmp(&b.song_id)));
+    because_of.truncate(max_evidence);
+    Explanation { because_of, shared_listeners: reached.len() }
+}
+
+//function to attach explanations to a list of recommendations made from the given seeds
+pub fn explain_all(recommendations: Vec<Recommendation>, seeds: &[String], graph: &ListenGraph, catalog: &SongCatalog, max_evidence: usize) -> Vec<Recommendation> {
+    recommendations
+        .into_iter()
+        .map(|mut recommendation| {
+            recommendation.explanation = Some(explain(&recommendation.song_id, seeds, graph, catalog, max_evidence));
+            recommendation
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::MSD;
+    use crate::test_data::record;
+
+    fn fake_data() -> Vec<MSD> {
+        vec![
+            record("user1", "S1", "A1", "Seed One", 1),
+            record("user1", "S3", "A2", "Target", 1),
+            record("user2", "S1", "A1", "Seed One", 1),
+            record("user2", "S2", "A1", "Seed Two", 1),
+            record("user2", "S3", "A2", "Target", 1),
+            record("user3", "S2", "A1", "Seed Two", 1),
+            record("user4", "S3", "A2", "Target", 1),
+        ]
+    }
+
+    #[test]
+    fn test_explain() {
+        let data = fake_data();
+        let graph = ListenGraph::new(&data);
+        let catalog = SongCatalog::new(&data);
+        let explanation = explain("S3", &["S1".to_string(), "S2".to_string()], &graph, &catalog, 5);
+
+        //S1 shares user1 and user2 with S3, S2 shares only user2
+        assert_eq!(explanation.because_of[0], Evidence { song_id: "S1".to_string(), title: "Seed One".to_string(), shared_listeners: 2 });
+        assert_eq!(explanation.because_of[1].shared_listeners, 1);
+        //user2 is counted once even though they played both seeds
+        assert_eq!(explanation.shared_listeners, 2);
+        assert_eq!(explanation.sentence(), "because you listened to 'Seed One' (2 shared listeners) and 'Seed Two' (1 shared listeners)");
+
+        //no user ids anywhere in the output
+        let json = serde_json::to_string(&explanation).unwrap();
+        assert!(!json.contains("user"));
+    }
+}
@@ -0,0 +1,51 @@
+use crate::exclusions::Exclusions;
+use crate::interactions::Interactions;
+use crate::linalg::{cosine, dot};
+use crate::scoring::{top_scores, Ranker};
+use std::collections::HashMap;
+
+//user and song embeddings learned by a matrix model (ALS, BPR, ...), numbered the same way as the Interactions they came from
+//a user's predicted preference for a song is the dot product of their two vectors
+pub struct FactorModel {
+    pub user_factors: Vec<Vec<f64>>, //one vector per user row
+    pub item_factors: Vec<Vec<f64>>, //one vector per song column
+}
+
+impl FactorModel {
+    //predicted preference of user row for song column
+    pub fn score(&self, user: usize, song: usize) -> f64 {
+        dot(&self.user_factors[user], &self.item_factors[song])
+    }
+
+    //function to recommend the k best scoring songs the user has not heard yet and that are not excluded (empty for an unknown user)
+    pub fn recommend(&self, interactions: &Interactions, user_id: &str, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+        let user = match interactions.user_index.get(user_id) {
+            Some(&user) => user,
+            None => return Vec::new(),
+        };
+
+        let mut song_score: HashMap<String, f64> = HashMap::new();
+        for song in 0..interactions.num_songs() {
+            if !interactions.has_listened(user, song) && !exclusions.excludes_song(&interactions.song_ids[song]) {
+                song_score.insert(interactions.song_ids[song].clone(), self.score(user, song));
+            }
+        }
+        top_scores(song_score, ranker, k)
+    }
+
+    //function to find the k songs whose vectors point the same way as the seed song's (cosine similarity)
+    pub fn similar_songs(&self, interactions: &Interactions, song_id: &str, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+        let seed = match interactions.song_index.get(song_id) {
+            Some(&seed) => seed,
+            None => return Vec::new(),
+        };
+
+        let mut song_score: HashMap<String, f64> = HashMap::new();
+        for song in 0..interactions.num_songs() {
+            if song != seed && !exclusions.excludes_song(&interactions.song_ids[song]) {
+                song_score.insert(interactions.song_ids[song].clone(), cosine(&self.item_factors[seed], &self.item_factors[song]));
+            }
+        }
+        top_scores(song_score, ranker, k)
+    }
+}
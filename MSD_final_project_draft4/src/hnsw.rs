@@ -0,0 +1,469 @@
+use crate::exclusions::Exclusions;
+use crate::linalg::dot;
+use crate::node2vec::SongEmbeddings;
+use crate::rng::Rng;
+use crate::scoring::Ranker;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+
+//settings for the HNSW index (Malkov and Yashunin, "Efficient and robust approximate nearest neighbor search
+//using Hierarchical Navigable Small World graphs")
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HnswConfig {
+    pub m: usize,               //links per song on the upper layers (layer 0 gets 2 * m)
+    pub ef_construction: usize, //candidates kept while inserting, higher builds a better graph more slowly
+    pub ef_search: usize,       //candidates kept while searching, higher is more accurate and slower
+    pub seed: u64,              //seed for picking each song's top layer
+}
+
+impl Default for HnswConfig {
+    fn default() -> HnswConfig {
+        HnswConfig { m: 16, ef_construction: 200, ef_search: 50, seed: 42 }
+    }
+}
+
+//a song and its distance to the query, ordered by distance and then song number so searches are repeatable
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Candidate {
+    distance: f64,
+    node: usize,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Candidate) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Candidate) -> Ordering {
+        self.distance.total_cmp(&other.distance).then_with(|| self.node.cmp(&other.node))
+    }
+}
+
+//approximate nearest neighbour index over unit-length song vectors (distance is 1 - cosine)
+pub struct HnswIndex {
+    pub config: HnswConfig,
+    pub song_ids: Vec<String>,
+    pub song_index: HashMap<String, usize>,
+    vectors: Vec<Vec<f64>>,       //normalized copies of the song vectors
+    links: Vec<Vec<Vec<usize>>>, //links[song][layer] = neighbours on that layer
+    entry_point: usize,
+}
+
+fn normalize(vector: &[f64]) -> Vec<f64> {
+    let norm = dot(vector, vector).sqrt();
+    if norm == 0.0 { vector.to_vec() } else { vector.iter().map(|value| value / norm).collect() }
+}
+
+//FNV-1a hash of the song_ids and the exact bits of every vector, saved in the header so a damaged or
+//edited file is caught when it is loaded
+fn fingerprint(song_ids: &[String], vectors: &[Vec<f64>]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut add = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+    for (song_id, vector) in song_ids.iter().zip(vectors) {
+        add(song_id.as_bytes());
+        add(&[0]); //separator so "S1" + "2..." is not "S12" + "..."
+        for value in vector {
+            add(&value.to_bits().to_le_bytes());
+        }
+    }
+    hash
+}
+
+impl HnswIndex {
+    fn distance(&self, query: &[f64], node: usize) -> f64 {
+        1.0 - dot(query, &self.vectors[node])
+    }
+
+    fn top_layer(&self) -> usize {
+        self.links.get(self.entry_point).map_or(0, |layers| layers.len() - 1)
+    }
+
+    //best-first search on one layer, returns up to ef closest songs found, closest first
+    fn search_layer(&self, query: &[f64], entry: usize, ef: usize, layer: usize) -> Vec<Candidate> {
+        let start = Candidate { distance: self.distance(query, entry), node: entry };
+        let mut visited: HashSet<usize> = HashSet::new();
+        visited.insert(entry);
+        let mut to_visit = BinaryHeap::new(); //closest first
+        to_visit.push(Reverse(start));
+        let mut found = BinaryHeap::new(); //furthest first, so the worst result is easy to drop
+        found.push(start);
+
+        while let Some(Reverse(current)) = to_visit.pop() {
+            let furthest = *found.peek().unwrap();
+            if current.distance > furthest.distance && found.len() >= ef {
+                break; //everything left is further than what we already have
+            }
+            for &neighbour in &self.links[current.node][layer] {
+                if visited.insert(neighbour) {
+                    let candidate = Candidate { distance: self.distance(query, neighbour), node: neighbour };
+                    if found.len() < ef || candidate < *found.peek().unwrap() {
+                        to_visit.push(Reverse(candidate));
+                        found.push(candidate);
+                        if found.len() > ef {
+                            found.pop();
+                        }
+                    }
+                }
+            }
+        }
+        found.into_sorted_vec()
+    }
+
+    //greedy descent through the upper layers, then a wide search on layer `down_to`
+    fn search_from_top(&self, query: &[f64], down_to: usize, ef: usize) -> Vec<Candidate> {
+        let mut entry = self.entry_point;
+        for layer in (down_to + 1..=self.top_layer()).rev() {
+            entry = self.search_layer(query, entry, 1, layer)[0].node;
+        }
+        self.search_layer(query, entry, ef, down_to)
+    }
+
+    //keeps only the closest `limit` links of a song on a layer
+    fn prune(&mut self, node: usize, layer: usize, limit: usize) {
+        if self.links[node][layer].len() <= limit {
+            return;
+        }
+        let query = self.vectors[node].clone();
+        let mut candidates: Vec<Candidate> = self.links[node][layer].iter().map(|&other| Candidate { distance: self.distance(&query, other), node: other }).collect();
+        candidates.sort();
+        self.links[node][layer] = candidates.into_iter().take(limit).map(|candidate| candidate.node).collect();
+    }
+
+    fn insert(&mut self, node: usize, level: usize) {
+        self.links.push(vec![Vec::new(); level + 1]);
+        if node == 0 {
+            self.entry_point = 0;
+            return;
+        }
+
+        let query = self.vectors[node].clone();
+        let top = self.top_layer();
+        let mut entry = self.entry_point;
+        for layer in (level + 1..=top).rev() {
+            entry = self.search_layer(&query, entry, 1, layer)[0].node;
+        }
+
+        for layer in (0..=level.min(top)).rev() {
+            let found = self.search_layer(&query, entry, self.config.ef_construction, layer);
+            let limit = if layer == 0 { 2 * self.config.m } else { self.config.m };
+            let neighbours: Vec<usize> = found.iter().take(self.config.m).map(|candidate| candidate.node).collect();
+            for &neighbour in &neighbours {
+                self.links[neighbour][layer].push(node);
+                self.prune(neighbour, layer, limit);
+            }
+            self.links[node][layer] = neighbours;
+            entry = found[0].node;
+        }
+
+        if level > top {
+            self.entry_point = node; //new highest song becomes the way in
+        }
+    }
+
+    //function to build the index over every song vector, songs are added in song_id order so the graph is repeatable
+    pub fn build(embeddings: &SongEmbeddings, config: &HnswConfig) -> HnswIndex {
+        let mut rng = Rng::new(config.seed);
+        let mut index = HnswIndex {
+            config: *config,
+            song_ids: embeddings.song_ids.clone(),
+            song_index: embeddings.song_index.clone(),
+            vectors: embeddings.vectors.iter().map(|vector| normalize(vector)).collect(),
+            links: Vec::new(),
+            entry_point: 0,
+        };
+        let level_scale = 1.0 / (config.m.max(2) as f64).ln();
+        for node in 0..index.vectors.len() {
+            let level = (-(1.0 - rng.next_f64()).ln() * level_scale) as usize; //most songs only live on layer 0
+            index.insert(node, level);
+        }
+        index
+    }
+
+    //function to find the k songs closest to a vector, returns (song number, cosine) best first
+    fn search(&self, query: &[f64], k: usize, ef: usize) -> Vec<(usize, f64)> {
+        if self.vectors.is_empty() {
+            return Vec::new();
+        }
+        let query = normalize(query);
+        self.search_from_top(&query, 0, ef.max(k)).into_iter().take(k).map(|candidate| (candidate.node, 1.0 - candidate.distance)).collect()
+    }
+
+    //function to find the k songs most similar to a song_id, returns (song_id, cosine) best first
+    //excluded songs are dropped from the search results, and the search is widened until k songs are left or the index runs out.
+    //Songs at the same distance are ordered by the ranker's policy
+    pub fn nearest(&self, song_id: &str, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+        let seed = match self.song_index.get(song_id) {
+            Some(&seed) => seed,
+            None => return Vec::new(),
+        };
+        let mut wanted = k + 1;
+        loop {
+            let found: Vec<(String, f64)> = self
+                .search(&self.vectors[seed], wanted, self.config.ef_search)
+                .into_iter()
+                .filter(|&(node, _)| node != seed && !exclusions.excludes_song(&self.song_ids[node]))
+                .map(|(node, score)| (self.song_ids[node].clone(), score))
+                .collect();
+            if found.len() >= k || wanted >= self.vectors.len() {
+                return ranker.rerank(found, k);
+            }
+            wanted *= 2;
+        }
+    }
+
+    //exact k nearest songs by checking every vector (what the index is approximating)
+    fn exact_nearest(&self, seed: usize, k: usize) -> Vec<usize> {
+        let mut all: Vec<Candidate> = (0..self.vectors.len())
+            .filter(|&node| node != seed)
+            .map(|node| Candidate { distance: self.distance(&self.vectors[seed], node), node })
+            .collect();
+        all.sort();
+        all.into_iter().take(k).map(|candidate| candidate.node).collect()
+    }
+
+    //function to measure recall@k: the share of the true k nearest songs the index also returns
+    //checks `queries` songs spread evenly through the index
+    pub fn recall(&self, k: usize, queries: usize) -> f64 {
+        let total = self.vectors.len();
+        if total < 2 || queries == 0 {
+            return 1.0;
+        }
+        let step = (total / queries).max(1);
+        let mut hits = 0;
+        let mut wanted = 0;
+        for seed in (0..total).step_by(step).take(queries) {
+            let exact: HashSet<usize> = self.exact_nearest(seed, k).into_iter().collect();
+            let approximate: HashSet<usize> = self.nearest(&self.song_ids[seed], &Exclusions::new(), &Ranker::default(), k).iter().map(|(song_id, _)| self.song_index[song_id]).collect();
+            hits += exact.intersection(&approximate).count();
+            wanted += exact.len();
+        }
+        if wanted == 0 { 1.0 } else { hits as f64 / wanted as f64 }
+    }
+
+    //function to check that a saved index was built from these embeddings with this config, so it can be reused
+    //ef_search only changes how searches run, so it does not have to match (set it on the index instead)
+    pub fn matches(&self, embeddings: &SongEmbeddings, config: &HnswConfig) -> bool {
+        self.config.m == config.m
+            && self.config.ef_construction == config.ef_construction
+            && self.config.seed == config.seed
+            && self.song_ids == embeddings.song_ids
+            && self.vectors.len() == embeddings.vectors.len()
+            && self.vectors.iter().zip(&embeddings.vectors).all(|(saved, current)| *saved == normalize(current))
+    }
+
+    //function to save the index as text: a header line, one "song_id v1 v2 ..." line per song,
+    //then one "song layer n1 n2 ..." line per song per layer
+    //the header is "hnsw m ef_construction ef_search seed entry_point songs dimensions fingerprint"
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let dimensions = self.vectors.first().map_or(0, |vector| vector.len());
+        let c = &self.config;
+        writeln!(
+            writer,
+            "hnsw {} {} {} {} {} {} {} {}",
+            c.m,
+            c.ef_construction,
+            c.ef_search,
+            c.seed,
+            self.entry_point,
+            self.vectors.len(),
+            dimensions,
+            fingerprint(&self.song_ids, &self.vectors)
+        )?;
+        for (song_id, vector) in self.song_ids.iter().zip(&self.vectors) {
+            let values: Vec<String> = vector.iter().map(|value| value.to_string()).collect();
+            writeln!(writer, "{} {}", song_id, values.join(" "))?;
+        }
+        for (node, layers) in self.links.iter().enumerate() {
+            for (layer, neighbours) in layers.iter().enumerate() {
+                let neighbours: Vec<String> = neighbours.iter().map(|neighbour| neighbour.to_string()).collect();
+                writeln!(writer, "{} {} {}", node, layer, neighbours.join(" "))?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    //function to load an index written by save
+    //everything a search indexes into is checked first, so a truncated or damaged file is an Err and not a panic later
+    pub fn load(path: &str) -> Result<HnswIndex, Box<dyn Error>> {
+        let mut lines = BufReader::new(File::open(path)?).lines();
+        let header = lines.next().ok_or("empty index file")??;
+        let fields: Vec<&str> = header.split(' ').collect();
+        if fields.len() != 9 || fields[0] != "hnsw" {
+            return Err("not an hnsw index file".into());
+        }
+        let config = HnswConfig { m: fields[1].parse()?, ef_construction: fields[2].parse()?, ef_search: fields[3].parse()?, seed: fields[4].parse()? };
+        let entry_point: usize = fields[5].parse()?;
+        let count: usize = fields[6].parse()?;
+        let dimensions: usize = fields[7].parse()?;
+        let saved_fingerprint: u64 = fields[8].parse()?;
+        if count > 0 && entry_point >= count {
+            return Err("entry point is not a song in the index file".into());
+        }
+
+        let mut song_ids = Vec::with_capacity(count);
+        let mut vectors = Vec::with_capacity(count);
+        for _ in 0..count {
+            let line = lines.next().ok_or("index file is missing vectors")??;
+            let mut parts = line.split(' ');
+            song_ids.push(parts.next().unwrap_or_default().to_string());
+            let vector = parts.map(|value| value.parse()).collect::<Result<Vec<f64>, _>>()?;
+            if vector.len() != dimensions {
+                return Err("vector with the wrong dimension in index file".into());
+            }
+            vectors.push(vector);
+        }
+        if fingerprint(&song_ids, &vectors) != saved_fingerprint {
+            return Err("song_ids or vectors in index file do not match its header".into());
+        }
+
+        let mut links: Vec<Vec<Vec<usize>>> = vec![Vec::new(); count];
+        for line in lines {
+            let line = line?;
+            let numbers: Vec<usize> = line.split_whitespace().map(|value| value.parse()).collect::<Result<Vec<usize>, _>>()?;
+            if numbers.len() < 2 || numbers[0] >= count || numbers[1] != links[numbers[0]].len() {
+                return Err("bad link line in index file".into()); //unknown song, or a layer out of order
+            }
+            if numbers[2..].iter().any(|&neighbour| neighbour >= count) {
+                return Err("link to a song that is not in the index file".into());
+            }
+            links[numbers[0]].push(numbers[2..].to_vec()); //layers are written in order
+        }
+
+        //every song needs layer 0, nothing can be above the entry point, and a link on a layer has to go to a song on that layer
+        if links.iter().any(|layers| layers.is_empty()) {
+            return Err("index file is missing link lines".into());
+        }
+        let top = links.get(entry_point).map_or(0, |layers| layers.len());
+        if links.iter().any(|layers| layers.len() > top) {
+            return Err("song above the entry point in index file".into());
+        }
+        for layers in &links {
+            for (layer, neighbours) in layers.iter().enumerate() {
+                if neighbours.iter().any(|&neighbour| links[neighbour].len() <= layer) {
+                    return Err("link to a song that is not on that layer in index file".into());
+                }
+            }
+        }
+
+        let song_index = song_ids.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect();
+        Ok(HnswIndex { config, song_ids, song_index, vectors, links, entry_point })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //random 8 dimensional vectors for 300 songs
+    fn fake_embeddings() -> SongEmbeddings {
+        let mut rng = Rng::new(7);
+        let song_ids: Vec<String> = (0..300).map(|i| format!("S{:03}", i)).collect();
+        let song_index = song_ids.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect();
+        let vectors = (0..300).map(|_| (0..8).map(|_| rng.normal()).collect()).collect();
+        SongEmbeddings { song_ids, song_index, vectors }
+    }
+
+    #[test]
+    fn test_hnsw_recall() {
+        let index = HnswIndex::build(&fake_embeddings(), &HnswConfig { m: 8, ef_construction: 100, ef_search: 50, seed: 1 });
+        let recall = index.recall(10, 30);
+        assert!(recall > 0.9, "recall {}", recall);
+        let nearest = index.nearest("S000", &Exclusions::new(), &Ranker::default(), 5);
+        assert_eq!(nearest.len(), 5);
+        assert!(nearest.iter().all(|(song, _)| song != "S000"));
+        assert!(index.nearest("missing", &Exclusions::new(), &Ranker::default(), 5).is_empty());
+
+        //excluding the 5 nearest still gives 5 songs, none of them excluded
+        let excluded = Exclusions::new().with_songs(nearest.iter().map(|(song, _)| song.clone()));
+        let next = index.nearest("S000", &excluded, &Ranker::default(), 5);
+        assert_eq!(next.len(), 5);
+        assert!(next.iter().all(|(song, _)| !excluded.excludes_song(song)));
+    }
+
+    #[test]
+    fn test_hnsw_save_and_load() {
+        let index = HnswIndex::build(&fake_embeddings(), &HnswConfig { m: 4, ef_construction: 20, ef_search: 20, seed: 1 });
+        let path = std::env::temp_dir().join("hnsw_test.index");
+        index.save(path.to_str().unwrap()).unwrap();
+        let loaded = HnswIndex::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.config, index.config);
+        assert_eq!(loaded.nearest("S010", &Exclusions::new(), &Ranker::default(), 5), index.nearest("S010", &Exclusions::new(), &Ranker::default(), 5));
+    }
+
+    #[test]
+    fn test_hnsw_matches() {
+        let config = HnswConfig { m: 4, ef_construction: 20, ef_search: 20, seed: 1 };
+        let embeddings = fake_embeddings();
+        let path = std::env::temp_dir().join("hnsw_matches_test.index");
+        HnswIndex::build(&embeddings, &config).save(path.to_str().unwrap()).unwrap();
+        let loaded = HnswIndex::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(loaded.matches(&embeddings, &config));
+        assert!(loaded.matches(&embeddings, &HnswConfig { ef_search: 80, ..config })); //search setting only
+        assert!(!loaded.matches(&embeddings, &HnswConfig { m: 8, ..config }));
+        //new vectors for the same songs
+        let mut changed = fake_embeddings();
+        changed.vectors[3][0] += 1.0;
+        assert!(!loaded.matches(&changed, &config));
+        //a song more or less
+        let mut fewer = fake_embeddings();
+        fewer.song_ids.pop();
+        fewer.vectors.pop();
+        assert!(!loaded.matches(&fewer, &config));
+    }
+
+    #[test]
+    fn test_hnsw_load_corrupt() {
+        let index = HnswIndex::build(&fake_embeddings(), &HnswConfig { m: 4, ef_construction: 20, ef_search: 20, seed: 1 });
+        let path = std::env::temp_dir().join("hnsw_corrupt_test.index");
+        let path = path.to_str().unwrap();
+        index.save(path).unwrap();
+        let saved = std::fs::read_to_string(path).unwrap();
+        let lines: Vec<&str> = saved.lines().collect();
+        let header: Vec<&str> = lines[0].split(' ').collect();
+        let with_header = |field: usize, value: &str| {
+            let mut fields = header.clone();
+            fields[field] = value;
+            std::iter::once(fields.join(" ")).chain(lines[1..].iter().map(|line| line.to_string())).collect::<Vec<String>>().join("\n")
+        };
+        let first_link = 301; //header and 300 vector lines come first
+        let corrupt = [
+            //truncated in the middle of the links
+            lines[..lines.len() / 2].join("\n"),
+            //truncated in the middle of the vectors
+            lines[..150].join("\n"),
+            //entry point past the last song
+            with_header(5, "300"),
+            //dimension that does not match the vectors
+            with_header(7, "9"),
+            //vector changed after saving
+            saved.replacen(&lines[1][..10], "S999 0.123", 1),
+            //neighbour past the last song
+            saved.replacen(lines[first_link], "0 0 300", 1),
+            //song 0 loses its layer 0
+            lines.iter().enumerate().filter(|(i, _)| *i != first_link).map(|(_, line)| *line).collect::<Vec<&str>>().join("\n"),
+        ];
+        for (case, text) in corrupt.iter().enumerate() {
+            std::fs::write(path, text).unwrap();
+            assert!(HnswIndex::load(path).is_err(), "corrupt case {} loaded", case);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+use crate::csv_reader::MSD;
+use std::collections::HashMap;
+
+//the user x song listen_count matrix, stored sparsely with users and songs numbered 0..n
+//ids are sorted before numbering so the same data always gives the same matrix (the models depend on that to be reproducible)
+pub struct Interactions {
+    pub user_ids: Vec<String>,             //row number -> user_id
+    pub song_ids: Vec<String>,             //column number -> song_id
+    pub user_index: HashMap<String, usize>, //user_id -> row number
+    pub song_index: HashMap<String, usize>, //song_id -> column number
+    pub by_user: Vec<Vec<(usize, f64)>>,   //for each user, (song number, plays) sorted by song number
+    pub by_song: Vec<Vec<(usize, f64)>>,   //for each song, (user number, plays) sorted by user number
+}
+
+impl Interactions {
+    pub fn new(data: &[MSD]) -> Interactions {
+        let mut user_ids: Vec<String> = data.iter().map(|record| record.user_id.clone()).collect();
+        user_ids.sort();
+        user_ids.dedup();
+        let mut song_ids: Vec<String> = data.iter().map(|record| record.song_id.clone()).collect();
+        song_ids.sort();
+        song_ids.dedup();
+
+        let user_index: HashMap<String, usize> = user_ids.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect();
+        let song_index: HashMap<String, usize> = song_ids.iter().enumerate().map(|(i, id)| (id.clone(), i)).collect();
+
+        //add up plays per (user, song) in case a song has several rows for the same user
+        let mut plays: HashMap<(usize, usize), f64> = HashMap::new();
+        for record in data {
+            *plays.entry((user_index[&record.user_id], song_index[&record.song_id])).or_insert(0.0) += record.plays() as f64;
+        }
+
+        let mut by_user: Vec<Vec<(usize, f64)>> = vec![Vec::new(); user_ids.len()];
+        let mut by_song: Vec<Vec<(usize, f64)>> = vec![Vec::new(); song_ids.len()];
+        for (&(user, song), &count) in &plays {
+            by_user[user].push((song, count));
+            by_song[song].push((user, count));
+        }
+        for row in by_user.iter_mut().chain(by_song.iter_mut()) {
+            row.sort_by_key(|&(index, _)| index);
+        }
+
+        Interactions { user_ids, song_ids, user_index, song_index, by_user, by_song }
+    }
+
+    pub fn num_users(&self) -> usize {
+        self.user_ids.len()
+    }
+
+    pub fn num_songs(&self) -> usize {
+        self.song_ids.len()
+    }
+
+    //true if the user (row number) has played the song (column number)
+    pub fn has_listened(&self, user: usize, song: usize) -> bool {
+        self.by_user[user].binary_search_by_key(&song, |&(index, _)| index).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::record;
+
+    #[test]
+    fn test_interactions() {
+        let data = vec![
+            record("userB", "S2", "A1", "Song 2", 3),
+            record("userA", "S1", "A1", "Song 1", 1),
+            record("userA", "S1", "A1", "Song 1", 4), //second row for the same listen is added
+            record("userB", "S1", "A1", "Song 1", 2),
+        ];
+        let interactions = Interactions::new(&data);
+
+        assert_eq!(interactions.user_ids, vec!["userA", "userB"]);
+        assert_eq!(interactions.song_ids, vec!["S1", "S2"]);
+        assert_eq!(interactions.by_user[0], vec![(0, 5.0)]);
+        assert_eq!(interactions.by_song[0], vec![(0, 5.0), (1, 2.0)]);
+        assert!(interactions.has_listened(1, 1));
+        assert!(!interactions.has_listened(0, 1));
+    }
+}
@@ -0,0 +1,183 @@
+//the bits of linear algebra the matrix models need, on plain Vec<f64> rows
+
+pub fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+//cosine similarity of two vectors (0 if either is all zeros)
+pub fn cosine(a: &[f64], b: &[f64]) -> f64 {
+    let norms = dot(a, a).sqrt() * dot(b, b).sqrt();
+    if norms == 0.0 { 0.0 } else { dot(a, b) / norms }
+}
+
+//Cholesky factorization of a symmetric positive definite matrix, returns the lower triangle L with A = L * L^T
+//returns None if the matrix is not positive definite
+pub fn cholesky(a: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = a.len();
+    let mut l = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in 0..=i {
+            let sum = a[i][j] - dot(&l[i][..j], &l[j][..j]);
+            if i == j {
+                if sum <= 0.0 {
+                    return None;
+                }
+                l[i][i] = sum.sqrt();
+            } else {
+                l[i][j] = sum / l[j][j];
+            }
+        }
+    }
+    Some(l)
+}
+
+//solves A x = b for a symmetric positive definite A using its Cholesky factor
+pub fn solve_spd(a: &[Vec<f64>], b: &[f64]) -> Option<Vec<f64>> {
+    let l = cholesky(a)?;
+    let n = b.len();
+
+    //forward substitution: L y = b
+    let mut y = vec![0.0; n];
+    for i in 0..n {
+        y[i] = (b[i] - dot(&l[i][..i], &y[..i])) / l[i][i];
+    }
+    //back substitution: L^T x = y
+    let mut x = vec![0.0; n];
+    for i in (0..n).rev() {
+        let mut sum = y[i];
+        for k in i + 1..n {
+            sum -= l[k][i] * x[k];
+        }
+        x[i] = sum / l[i][i];
+    }
+    Some(x)
+}
+
+//inverse of a symmetric positive definite matrix, one Cholesky solve per column
+pub fn invert_spd(a: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let n = a.len();
+    let l = cholesky(a)?;
+    let mut inverse = vec![vec![0.0; n]; n];
+    for column in 0..n {
+        //L y = e_column, then L^T x = y
+        let mut y = vec![0.0; n];
+        for i in column..n { //y is zero above the column
+            let e = if i == column { 1.0 } else { 0.0 };
+            y[i] = (e - dot(&l[i][column..i], &y[column..i])) / l[i][i];
+        }
+        for i in (0..n).rev() {
+            let mut sum = y[i];
+            for k in i + 1..n {
+                sum -= l[k][i] * inverse[k][column];
+            }
+            inverse[i][column] = sum / l[i][i];
+        }
+    }
+    Some(inverse)
+}
+
+//modified Gram-Schmidt on the columns of a row-major matrix, so the columns become orthonormal
+//columns that are (close to) a mix of the earlier ones are set to zero
+pub fn orthonormalize_columns(m: &mut [Vec<f64>]) {
+    let columns = m.first().map_or(0, |row| row.len());
+    for c in 0..columns {
+        for earlier in 0..c {
+            let projection: f64 = m.iter().map(|row| row[c] * row[earlier]).sum();
+            for row in m.iter_mut() {
+                row[c] -= projection * row[earlier];
+            }
+        }
+        let norm: f64 = m.iter().map(|row| row[c] * row[c]).sum::<f64>().sqrt();
+        for row in m.iter_mut() {
+            row[c] = if norm > 1e-10 { row[c] / norm } else { 0.0 };
+        }
+    }
+}
+
+//eigenvalues and eigenvectors of a small symmetric matrix with the cyclic Jacobi method
+//returns (eigenvalues, eigenvectors) sorted from largest eigenvalue down, eigenvectors[k] goes with eigenvalues[k]
+pub fn symmetric_eigen(a: &[Vec<f64>]) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let n = a.len();
+    let mut a = a.to_vec();
+    let mut v: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect();
+
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n).flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j))).map(|(i, j)| a[i][j] * a[i][j]).sum();
+        if off_diagonal < 1e-22 {
+            break;
+        }
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+                //rotation angle that zeroes a[p][q]
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let t = if theta == 0.0 { 1.0 } else { t };
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (row_p, row_q) = (a[p].clone(), a[q].clone());
+                for (k, (apk, aqk)) in row_p.iter().zip(&row_q).enumerate() {
+                    a[p][k] = c * apk - s * aqk;
+                    a[q][k] = s * apk + c * aqk;
+                }
+                for row in v.iter_mut() {
+                    let (vkp, vkq) = (row[p], row[q]);
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&x, &y| a[y][y].total_cmp(&a[x][x]));
+    let values = order.iter().map(|&k| a[k][k]).collect();
+    let vectors = order.iter().map(|&k| v.iter().map(|row| row[k]).collect()).collect();
+    (values, vectors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve_spd() {
+        let a = vec![vec![4.0, 1.0], vec![1.0, 3.0]];
+        let x = solve_spd(&a, &[1.0, 2.0]).unwrap();
+        //4x + y = 1, x + 3y = 2 -> x = 1/11, y = 7/11
+        assert!((x[0] - 1.0 / 11.0).abs() < 1e-12);
+        assert!((x[1] - 7.0 / 11.0).abs() < 1e-12);
+        //not positive definite
+        assert!(solve_spd(&[vec![0.0]], &[1.0]).is_none());
+    }
+
+    #[test]
+    fn test_invert_spd() {
+        let a = vec![vec![4.0, 1.0, 0.5], vec![1.0, 3.0, 0.2], vec![0.5, 0.2, 2.0]];
+        let inverse = invert_spd(&a).unwrap();
+        //A * A^-1 is the identity
+        for (i, row) in a.iter().enumerate() {
+            for j in 0..3 {
+                let value: f64 = row.iter().zip(&inverse).map(|(x, inverse_row)| x * inverse_row[j]).sum();
+                assert!((value - if i == j { 1.0 } else { 0.0 }).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_symmetric_eigen() {
+        let (values, vectors) = symmetric_eigen(&[vec![2.0, 1.0], vec![1.0, 2.0]]);
+        assert!((values[0] - 3.0).abs() < 1e-10);
+        assert!((values[1] - 1.0).abs() < 1e-10);
+        //eigenvector for 3 is (1, 1) / sqrt(2), up to sign
+        assert!((vectors[0][0].abs() - 0.5f64.sqrt()).abs() < 1e-10);
+        assert!((vectors[0][0] - vectors[0][1]).abs() < 1e-10);
+    }
+}
@@ -0,0 +1,45 @@
+use crate::csv_reader::MSD;
+use std::collections::{HashMap, HashSet};
+
+//the user-song bipartite graph, indexed both ways so lookups do not have to scan the whole MSD each time
+//(songs_to_users and users_to_songs loop over every record per call, which is fine once but slow in a loop)
+pub struct ListenGraph {
+    pub song_users: HashMap<String, HashSet<String>>, //song_id -> users who listened to it
+    pub user_songs: HashMap<String, HashSet<String>>, //user_id -> song_ids they listened to
+}
+
+impl ListenGraph {
+    pub fn new(data: &[MSD]) -> ListenGraph {
+        let mut song_users: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut user_songs: HashMap<String, HashSet<String>> = HashMap::new();
+        for record in data {
+            song_users.entry(record.song_id.clone()).or_default().insert(record.user_id.clone());
+            user_songs.entry(record.user_id.clone()).or_default().insert(record.song_id.clone());
+        }
+        ListenGraph { song_users, user_songs }
+    }
+
+    //number of users who listened to the song (0 if the song is not in the data)
+    pub fn listener_count(&self, song_id: &str) -> usize {
+        self.song_users.get(song_id).map_or(0, |users| users.len())
+    }
+
+    pub fn total_users(&self) -> usize {
+        self.user_songs.len()
+    }
+
+    //function to count, for every other song, how many of the seed's listeners also played it
+    pub fn co_listeners(&self, seed_song_id: &str) -> HashMap<String, usize> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        if let Some(users) = self.song_users.get(seed_song_id) {
+            for user in users {
+                for song in &self.user_songs[user] {
+                    if song != seed_song_id {
+                        *counts.entry(song.clone()).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+        counts
+    }
+}
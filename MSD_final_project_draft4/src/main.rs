@@ -1,8 +1,63 @@
+mod als;
+mod artists;
+mod association_rules;
+mod bpr;
+mod cold_start;
 mod csv_reader;
+mod dedup;
+mod diversity;
+mod ease;
+mod exclusions;
+mod expansion;
+mod explain;
+mod factors;
+mod hnsw;
+mod interactions;
+mod linalg;
+mod listen_graph;
+mod minhash;
+mod multi_seed;
+mod node2vec;
+mod popularity;
+mod random_walk;
+mod recommendation;
+mod rng;
+mod scoring;
+mod similarity;
+mod svd;
+mod user_knn;
+mod weighting;
+#[cfg(test)]
+mod test_data;
 
 use csv_reader::{read_msd, MSD};
+use als::{train_als, AlsConfig};
+use association_rules::{mine_rules, RuleConfig};
+use artists::{artist_names, find_artist_id, recommend_from_artist, similar_artists, song_artists};
+use bpr::{train_bpr, BprConfig, NegativeSampling};
+use cold_start::{cold_start, ColdStartConfig};
+use dedup::{merge_duplicates, resolve_title};
+use diversity::{diversify, DiversityConfig};
+use ease::{train_ease, EaseConfig};
+use exclusions::Exclusions;
+use expansion::{expand, ExpandedSong, ExpansionConfig};
+use explain::{explain, explain_all, Explanation};
+use hnsw::{HnswConfig, HnswIndex};
+use interactions::Interactions;
+use listen_graph::ListenGraph;
+use minhash::{similar_song_pairs, MinHashConfig};
+use multi_seed::{recommend_from_songs, SeedAggregation};
+use node2vec::{train_node2vec, Node2VecConfig};
+use popularity::{debias, Debiasing};
+use random_walk::{random_walk_with_restart, Seed, WalkConfig};
+use recommendation::{reranked, to_json, Method, Recommendation, Reranker, ScoreType, SongCatalog};
+use scoring::{co_listen_counts, top_scores, Ranker, TieBreak};
+use similarity::{similar_songs, song_similarities, ItemItem, Similarity};
+use svd::{train_svd, SvdConfig};
+use user_knn::{UserKnn, UserSimilarity};
+use weighting::{weighted_song_scores, Weighting};
+use std::cmp::Ordering;
 use std::collections::{HashSet, HashMap};
-use std::error::Error;
 use std::fs::File;
 
 //function to find users who have listened to inputed song 
@@ -40,32 +95,22 @@ fn users_to_songs(users: &HashSet<String>, data: &[MSD]) -> HashMap<String, Hash
     user_songs_hm //return hashmap
 }
 
-fn most_popular_song(user_songs_hm: &HashMap<String, HashSet<String>>, exclude_input: &str) -> Option<(String, usize)> {
-    let mut song_score: HashMap<String, usize> = HashMap::new(); //initialize hashset with song as key and popularity as value
-
-    for songs in user_songs_hm.values() { //iterates through hashsets in user_songs_hm
-        for song in songs { //iterate through each song in hashset
-            if song != exclude_input { //excludes input song from calculations
-                if let Some(count) = song_score.get_mut(song) { //attempt to use .get_mut to find song (key) in hashmap song_score
-                    *count += 1;  //if song exists as key in hashmap, add one to value (count)
-                } else {
-                    song_score.insert(song.clone(), 1);  //if not, add song to hashmap as key, with value (count) as 1
-                }
-            }
-        }
-    }
+//songs are titles here, so the catalog turns a title into the song_ids the exclusions are kept by
+//songs with the same count are ordered by the ranker's tie-break policy
+fn most_popular_song(user_songs_hm: &HashMap<String, HashSet<String>>, exclusions: &Exclusions, catalog: &SongCatalog, ranker: &Ranker) -> Option<(String, usize)> {
+    let song_score = co_listen_counts(user_songs_hm, |title| exclusions.excludes_title(title, catalog)); //song as key and number of users who listened as value (excluded songs left out)
 
-    let mut most_popular = None; //will become the tuple that stores the most popular song. Starts at none, it is an option.
+    let mut most_popular: Option<(String, usize)> = None; //will become the tuple that stores the most popular song. Starts at none, it is an option.
     let mut top_count = 0; //count to find most popular song
 
     for (song, count) in song_score {  //iterates through every song and count
         if count > top_count
             || (count == top_count && match &most_popular {
-                Some((song_name, _)) => song < *song_name, //if song is lexicographically smaller, current song is more popular
+                Some((song_name, _)) => ranker.title_tie_order(&song, song_name) == Ordering::Less, //if the tie-break puts song first, current song is more popular
                 None => true,
             })
         {
-            most_popular = Some((song, count)); //update most_popular if new song is more popular or has a lexicographically smaller name
+            most_popular = Some((song, count)); //update most_popular if new song is more popular or wins the tie-break
             top_count = count; //update the top count
         }
     }
@@ -73,23 +118,16 @@ fn most_popular_song(user_songs_hm: &HashMap<String, HashSet<String>>, exclude_i
     most_popular //returns tuple of most popular song
 }
 
-//function that reccomends songs if they do not have many users
-//it takes whatever users the input song has, finds the 3 most popular songs, finds every user that listened to those 3 songs, then finds the most popular songs among them
-fn find_more_songs(input_song: &str, data: &[MSD]) -> Option<(String, usize)> {
+//function to find the 3 most popular songs among the input song's listeners (the songs find_more_songs expands through)
+fn bridge_songs(input_song: &str, data: &[MSD], catalog: &SongCatalog, ranker: &Ranker) -> Vec<String> {
     let users = songs_to_users(input_song, data); //find users for input song
-
-    //this code only runs if there are not enough users that have listened to the input song (>= 5)
-    if users.len() >= 5 {
-        println!("Song is popular, no need for deeper analysis");
-        return None;
-    }
-
     let mut user_songs_hm = users_to_songs(&users, data); //find songs for each user who listened to the input song
+    let exclude_input = Exclusions::new().with_titles([input_song], catalog); //input song is never one of the top songs
 
     //call "fn most_popular" to find 3 most popular songs
     let mut top_songs: Vec<String> = vec![]; //intitialize vector to store top songs
     for _ in 0..3 { //underscore means value not needed
-        if let Some((most_popular, _)) = most_popular_song(&user_songs_hm, input_song) { //underscore means ignore second value in tuple
+        if let Some((most_popular, _)) = most_popular_song(&user_songs_hm, &exclude_input, catalog, ranker) { //underscore means ignore second value in tuple
             top_songs.push(most_popular.clone());
             for songs in user_songs_hm.values_mut() { //iterate through every song in hasmap
                 songs.remove(&most_popular); //remove the most popular song so that it is not included in the next iteration
@@ -98,6 +136,24 @@ fn find_more_songs(input_song: &str, data: &[MSD]) -> Option<(String, usize)> {
             break;
         }
     }
+    top_songs
+}
+
+//function that reccomends songs if they do not have many users
+//it takes whatever users the input song has, finds the 3 most popular songs, finds every user that listened to those 3 songs, then finds the most popular songs among them
+//(expansion::expand does the same walk with the threshold, number of songs and number of hops as settings)
+//returns the song, its number of listeners, and the bridge songs that led to it as the explanation
+fn find_more_songs(input_song: &str, data: &[MSD], graph: &ListenGraph, catalog: &SongCatalog, ranker: &Ranker) -> Option<(String, usize, Explanation)> {
+    let users = songs_to_users(input_song, data); //find users for input song
+
+    //this code only runs if there are not enough users that have listened to the input song (>= 5)
+    if users.len() >= 5 {
+        println!("Song is popular, no need for deeper analysis");
+        return None;
+    }
+
+    let top_songs = bridge_songs(input_song, data, catalog, ranker); //the 3 most popular songs among the input song's listeners
+
     //if no top songs found
     if top_songs.is_empty() {
         println!("No popular songs found");
@@ -115,18 +171,434 @@ fn find_more_songs(input_song: &str, data: &[MSD]) -> Option<(String, usize)> {
     let top_user_songs = users_to_songs(&top_users, data);
 
     //finds most popular songs for users 
-    if let Some((most_popular, count)) = most_popular_song(&top_user_songs, "EMPTY") { //input empty because exclude_input not needed
-        return Some((most_popular, count)); //return most popular song as tuple
-    } else {
-        return None;
+    let (song, count) = most_popular_song(&top_user_songs, &Exclusions::new(), catalog, ranker)?; //nothing excluded, most popular song as tuple
+    let bridge_ids: Vec<String> = top_songs.iter().filter_map(|bridge| catalog.song_id(bridge).cloned()).collect();
+    let explanation = explain(catalog.song_id(&song)?, &bridge_ids, graph, catalog, bridge_ids.len()); //every bridge that shares listeners, never the song itself
+    Some((song, count, explanation))
+}
+
+//prints recommendations one per line and hands them back for the JSON output
+fn report(recommendations: Vec<Recommendation>) -> Vec<Recommendation> {
+    for recommendation in &recommendations {
+        println!("{}", recommendation);
+    }
+    recommendations
+}
+
+//what every demo below works from: the data, the lookups built from it, and the input song, artist and user
+struct Demo<'a> {
+    data: &'a [MSD],
+    catalog: &'a SongCatalog, //fills in title, artist and listeners for every recommendation
+    graph: &'a ListenGraph,
+    interactions: Interactions, //the user x song matrix the trained models use
+    blocklist: Exclusions,      //songs and artists no recommender below may return, empty for now
+    ranker: Ranker<'a>,         //how every recommender below orders songs with the same score
+    input_song: String,
+    input_artist: &'a str,      //also used for the cold start fallback if nobody has played the input song
+    input_song_id: Option<String>,
+    input_seeds: Vec<String>,   //what the song-seeded results are explained by
+    shared_with_input: Option<HashMap<String, usize>>, //supporting listeners for seeded results
+    input_user: String,
+    history: Vec<String>,       //the input user's songs, explains the user's results
+}
+
+impl<'a> Demo<'a> {
+    //dedup may have renamed the input song (to "Imagine (Remastered)"), so the title is looked up again,
+    //and kept as typed if it is not in the data at all (cold start)
+    fn new(data: &'a [MSD], catalog: &'a SongCatalog, graph: &'a ListenGraph, input_song: &str, input_artist: &'a str) -> Demo<'a> {
+        let input_song = resolve_title(input_song, Some(input_artist), data).unwrap_or_else(|| input_song.to_string());
+        let input_song_id = catalog.song_id(&input_song).cloned();
+        let input_user = data.first().map(|record| record.user_id.clone()).unwrap_or_default(); //first user in the file
+        Demo {
+            data,
+            catalog,
+            graph,
+            interactions: Interactions::new(data),
+            blocklist: Exclusions::new(),
+            ranker: Ranker::new(TieBreak::Title, catalog), //most_popular_song has always put the alphabetically first title first on a tie
+            input_seeds: input_song_id.iter().cloned().collect(),
+            shared_with_input: input_song_id.as_ref().map(|song_id| graph.co_listeners(song_id)),
+            history: graph.user_songs.get(&input_user).map(|songs| songs.iter().cloned().collect()).unwrap_or_default(),
+            input_song,
+            input_artist,
+            input_song_id,
+            input_user,
+        }
+    }
+
+    //every recommendation below is built here: the catalog fills in each song, then the seeds explain it
+    //(a list with no seeds, like a cold start, has nothing to explain it by and is left without one)
+    fn recommend(&self, ranked: &[(String, f64)], method: Method, score_type: ScoreType, support: Option<&HashMap<String, usize>>, seeds: &[String], max_evidence: usize) -> Vec<Recommendation> {
+        let recommendations = self.catalog.recommendations(ranked, method, score_type, support);
+        if seeds.is_empty() {
+            return recommendations;
+        }
+        explain_all(recommendations, seeds, self.graph, self.catalog, max_evidence)
+    }
+
+    //songs recommended from the input song, supported by its co-listeners and explained by it
+    fn for_input_song(&self, ranked: &[(String, f64)], method: Method, score_type: ScoreType) -> Vec<Recommendation> {
+        self.recommend(ranked, method, score_type, self.shared_with_input.as_ref(), &self.input_seeds, 1)
+    }
+
+    //songs recommended for the input user, explained by the songs in their history they share the most listeners with
+    fn for_input_user(&self, ranked: &[(String, f64)], method: Method, score_type: ScoreType) -> Vec<Recommendation> {
+        self.recommend(ranked, method, score_type, None, &self.history, 2)
+    }
+}
+
+//most_popular_song among the input song's listeners (only works if more than 5 users)
+fn demo_most_popular(ctx: &Demo) -> Vec<Recommendation> {
+    let users = songs_to_users(&ctx.input_song, ctx.data);
+    if users.len() <= 5 {
+        println!("Song is not popular, looking for better recommendations...");
+        return Vec::new();
+    }
+    let user_songs_hm = users_to_songs(&users, ctx.data);
+    let exclusions = ctx.blocklist.clone().with_titles([ctx.input_song.as_str()], ctx.catalog);
+    let (song, count) = match most_popular_song(&user_songs_hm, &exclusions, ctx.catalog, &ctx.ranker) {
+        Some(most_popular) => most_popular,
+        None => return Vec::new(),
+    };
+    let song_id = match ctx.catalog.song_id(&song) {
+        Some(song_id) => song_id,
+        None => return Vec::new(),
+    };
+    let support = HashMap::from([(song_id.clone(), count)]); //count is the number of listeners
+    report(ctx.recommend(&[(song_id.clone(), count as f64)], Method::MostPopular, ScoreType::ListenerCount, Some(&support), &ctx.input_seeds, 1))
+}
+
+//find_more_songs, for input songs with fewer than 5 users
+fn demo_find_more_songs(ctx: &Demo) -> Vec<Recommendation> {
+    let (song, count, explanation) = match find_more_songs(&ctx.input_song, ctx.data, ctx.graph, ctx.catalog, &ctx.ranker) {
+        Some(found) => found,
+        None => return Vec::new(),
+    };
+    let song_id = match ctx.catalog.song_id(&song) {
+        Some(song_id) => song_id,
+        None => return Vec::new(),
+    };
+    let support = HashMap::from([(song_id.clone(), count)]); //count is the number of listeners
+    let mut recommended = ctx.recommend(&[(song_id.clone(), count as f64)], Method::FindMoreSongs, ScoreType::ListenerCount, Some(&support), &[], 0);
+    for recommendation in &mut recommended {
+        recommendation.explanation = Some(explanation.clone()); //the bridges find_more_songs went through
+    }
+    report(recommended)
+}
+
+//nobody (or hardly anybody) has played the input song: fall back to its artist, then similar artists, then the charts
+fn demo_cold_start(ctx: &Demo) -> Vec<Recommendation> {
+    let config = ColdStartConfig::default();
+    if !config.is_cold(songs_to_users(&ctx.input_song, ctx.data).len()) {
+        return Vec::new();
+    }
+    match cold_start(&ctx.input_song, Some(ctx.input_artist), ctx.data, ctx.graph, &config, &ctx.blocklist, &ctx.ranker, 3) {
+        Some(fallback) => {
+            println!("Cold start recommendations for '{}' ({:?}):", ctx.input_song, fallback.stage);
+            report(ctx.recommend(&fallback.songs, fallback.stage.method(), ScoreType::ListenerCount, None, &[], 0))
+        }
+        None => {
+            println!("No fallback recommendations for '{}'", ctx.input_song);
+            Vec::new()
+        }
+    }
+}
+
+//weighted recommendations, a user who played a song 500 times counts more than one who played it once
+fn demo_weighted(ctx: &Demo) -> Vec<Recommendation> {
+    let song_id = match &ctx.input_song_id {
+        Some(song_id) => song_id,
+        None => return Vec::new(),
+    };
+    let mut found = Vec::new();
+    for weighting in [Weighting::Raw, Weighting::Log, Weighting::Binary, Weighting::Confidence(40.0)] {
+        println!("Weighted ({:?}) recommendations for '{}':", weighting, ctx.input_song);
+        let ranked = weighted_song_scores(song_id, ctx.data, weighting, &ctx.blocklist, &ctx.ranker, 1);
+        found.extend(report(ctx.for_input_song(&ranked, Method::Weighted, ScoreType::WeightedCount)));
+    }
+    found
+}
+
+//similarity based recommendations, these reflect affinity to the input song instead of overall popularity
+fn demo_similarity(ctx: &Demo) -> Vec<Recommendation> {
+    let song_id = match &ctx.input_song_id {
+        Some(song_id) => song_id,
+        None => return Vec::new(),
+    };
+    let mut found = Vec::new();
+    for similarity in [Similarity::Jaccard, Similarity::Cosine, Similarity::Conditional(0.5), Similarity::Lift, Similarity::Pmi] {
+        println!("Most similar ({:?}) to '{}':", similarity, ctx.input_song);
+        let ranked = similar_songs(song_id, ctx.graph, similarity, &ctx.blocklist, &ctx.ranker, 1);
+        found.extend(report(ctx.for_input_song(&ranked, Method::ItemSimilarity, ScoreType::Similarity)));
+    }
+    found
+}
+
+//multi-hop expansion, the general version of find_more_songs (with no listener cutoff it works for popular songs too)
+fn demo_expansion(ctx: &Demo) -> Vec<Recommendation> {
+    let song_id = match &ctx.input_song_id {
+        Some(song_id) => song_id,
+        None => return Vec::new(),
+    };
+    let config = ExpansionConfig { hops: 3, fan_out: 3, min_support: 2, decay: 0.5, min_seed_listeners: usize::MAX };
+    let expanded: Vec<ExpandedSong> = expand(song_id, ctx.graph, &config, &ctx.blocklist, &ctx.ranker).into_iter().take(5).collect();
+    let ranked: Vec<(String, f64)> = expanded.iter().map(|found| (found.song_id.clone(), found.score)).collect();
+    let support: HashMap<String, usize> = expanded.iter().map(|found| (found.song_id.clone(), found.listeners)).collect();
+    let recommended = ctx.recommend(&ranked, Method::Expansion, ScoreType::ListenerCount, Some(&support), &ctx.input_seeds, 1);
+    for (recommendation, found) in recommended.iter().zip(&expanded) {
+        println!("{} from hop {}", recommendation, found.hop);
+    }
+    recommended
+}
+
+//discovery: the same co-listen counts most_popular_song uses, re-ranked away from the chart hits
+fn demo_long_tail(ctx: &Demo) -> Vec<Recommendation> {
+    let co_listened = match &ctx.shared_with_input {
+        Some(co_listened) => co_listened,
+        None => return Vec::new(),
+    };
+    let candidates = top_scores(co_listened.iter().map(|(song, count)| (song.clone(), *count as f64)).collect(), &ctx.ranker, co_listened.len());
+    let mut found = Vec::new();
+    for debiasing in [Debiasing::InversePopularity(0.8), Debiasing::BucketQuotas(vec![0, 1, 2]), Debiasing::MaxGlobalRank(20)] {
+        println!("Long-tail ({:?}) recommendations for '{}':", debiasing, ctx.input_song);
+        let score_type = if let Debiasing::InversePopularity(_) = debiasing { ScoreType::WeightedCount } else { ScoreType::ListenerCount };
+        let ranked = debias(&candidates, ctx.graph, &debiasing, &ctx.ranker, 3);
+        found.extend(report(reranked(ctx.for_input_song(&ranked, Method::MostPopular, score_type), debiasing.reranker())));
+    }
+    found
+}
+
+//the same co-listen ranking with each tie-break policy, songs with equal counts come out in a fixed order
+fn demo_tie_breaks(ctx: &Demo) -> Vec<Recommendation> {
+    let co_listened = match &ctx.shared_with_input {
+        Some(co_listened) => co_listened,
+        None => return Vec::new(),
+    };
+    let mut found = Vec::new();
+    for tie_break in [TieBreak::SongId, TieBreak::Title, TieBreak::Popularity] {
+        println!("Co-listened with '{}' (ties by {:?}):", ctx.input_song, tie_break);
+        let ranked: Vec<(String, f64)> = top_scores(co_listened.clone(), &Ranker::new(tie_break, ctx.catalog), 3).into_iter().map(|(song, count)| (song, count as f64)).collect();
+        found.extend(report(ctx.for_input_song(&ranked, Method::MostPopular, ScoreType::ListenerCount)));
+    }
+    found
+}
+
+//user-based recommendations, find the listeners most like the input user and use their songs
+fn demo_user_knn(ctx: &Demo) -> Vec<Recommendation> {
+    let knn = UserKnn::new(ctx.data, Weighting::Log);
+    let mut found = Vec::new();
+    for measure in [UserSimilarity::Cosine, UserSimilarity::Jaccard] {
+        println!("User-KNN ({:?}) recommendations for user {}:", measure, ctx.input_user);
+        let ranked = knn.recommend(&ctx.input_user, measure, 20, &ctx.blocklist, &ctx.ranker, 3);
+        found.extend(report(ctx.for_input_user(&ranked, Method::UserKnn, ScoreType::Similarity)));
+    }
+    found
+}
+
+//songs like the input song for the input user, leaving out the user's history and the input song's own artist
+fn demo_new_to_user(ctx: &Demo) -> Vec<Recommendation> {
+    let song_id = match &ctx.input_song_id {
+        Some(song_id) => song_id,
+        None => return Vec::new(),
+    };
+    let same_artist: Vec<String> = ctx.data.iter().filter(|record| &record.song_id == song_id).map(|record| record.artist_id.clone()).take(1).collect();
+    let exclusions = ctx.blocklist.clone().with_songs([song_id.clone()]).with_artists(same_artist, ctx.data).with_heard_by(&ctx.input_user, ctx.data);
+    println!("New to user {} and like '{}' (other artists only):", ctx.input_user, ctx.input_song);
+    let ranked = top_scores(exclusions.filter(song_similarities(song_id, ctx.graph, Similarity::Cosine)), &ctx.ranker, 3);
+    report(ctx.for_input_song(&ranked, Method::ItemSimilarity, ScoreType::Similarity))
+}
+
+//personalized recommendations from item-item similarity over the user's whole history
+fn demo_item_item(ctx: &Demo) -> Vec<Recommendation> {
+    println!("Item-item recommendations for user {}:", ctx.input_user);
+    let item_item = ItemItem { exclusions: ctx.blocklist.clone(), ranker: ctx.ranker, ..ItemItem::new(ctx.graph) };
+    report(ctx.for_input_user(&item_item.recommend_for_user(&ctx.input_user, 3), Method::ItemItem, ScoreType::Similarity)) //explained by the history songs each one came from
+}
+
+//matrix factorization (ALS) on the user x song listen_count matrix
+fn demo_als(ctx: &Demo) -> Vec<Recommendation> {
+    let als = train_als(&ctx.interactions, &AlsConfig::default());
+    println!("ALS recommendations for user {}:", ctx.input_user);
+    let mut found = report(ctx.for_input_user(&als.recommend(&ctx.interactions, &ctx.input_user, &ctx.blocklist, &ctx.ranker, 3), Method::Als, ScoreType::ModelScore));
+
+    //the same model with at most one song per artist and less alike songs, picked from its top 20
+    let artist_of_song = song_artists(ctx.data);
+    let candidates = als.recommend(&ctx.interactions, &ctx.input_user, &ctx.blocklist, &ctx.ranker, 20);
+    for relevance in [1.0, 0.5] {
+        let config = DiversityConfig { relevance, ..DiversityConfig::default() };
+        println!("Diverse ALS recommendations for user {} (relevance {}):", ctx.input_user, relevance);
+        let diverse = diversify(&candidates, ctx.graph, &artist_of_song, &config, &ctx.ranker, 3);
+        found.extend(report(reranked(ctx.for_input_user(&diverse, Method::Als, ScoreType::ModelScore), Reranker::Diversity)));
+    }
+
+    if let Some(song_id) = &ctx.input_song_id {
+        println!("Similar songs (ALS) to '{}':", ctx.input_song);
+        let ranked = als.similar_songs(&ctx.interactions, song_id, &ctx.blocklist, &ctx.ranker, 3);
+        found.extend(report(ctx.for_input_song(&ranked, Method::Als, ScoreType::Similarity)));
+    }
+    found
+}
+
+//pairwise ranking (BPR), popular negatives make the model work harder to separate songs users skip
+fn demo_bpr(ctx: &Demo) -> Vec<Recommendation> {
+    let config = BprConfig { negative_sampling: NegativeSampling::Popularity(0.75), ..BprConfig::default() };
+    let (bpr, aucs) = train_bpr(&ctx.interactions, &config);
+    for (epoch, auc) in aucs.iter().enumerate() {
+        println!("BPR epoch {} validation AUC {:.4}", epoch + 1, auc);
+    }
+    println!("BPR recommendations for user {}:", ctx.input_user);
+    report(ctx.for_input_user(&bpr.recommend(&ctx.interactions, &ctx.input_user, &ctx.blocklist, &ctx.ranker, 3), Method::Bpr, ScoreType::ModelScore))
+}
+
+//EASE item-item model on the most popular songs
+fn demo_ease(ctx: &Demo) -> Vec<Recommendation> {
+    let ease = match train_ease(&ctx.interactions, &EaseConfig::default()) {
+        Some(ease) => ease,
+        None => return Vec::new(),
+    };
+    println!("EASE recommendations for user {}:", ctx.input_user);
+    let found = report(ctx.for_input_user(&ease.recommend(&ctx.interactions, &ctx.input_user, &ctx.blocklist, &ctx.ranker, 3), Method::Ease, ScoreType::ModelScore));
+    //the weights are top_n x top_n, so they are only written out when asked for (EASE_WEIGHTS=ease_weights.csv)
+    if let Ok(path) = std::env::var("EASE_WEIGHTS") {
+        if ease.save_weights(&path).is_err() {
+            eprintln!("Problem saving EASE weights");
+        }
+    }
+    found
+}
+
+//PureSVD, latent song vectors from a truncated SVD of the user x song matrix
+fn demo_svd(ctx: &Demo) -> Vec<Recommendation> {
+    let (svd, singular_values) = train_svd(&ctx.interactions, &SvdConfig::default());
+    println!("Top singular values: {:?}", singular_values.iter().take(5).map(|value| format!("{:.2}", value)).collect::<Vec<String>>());
+    println!("PureSVD recommendations for user {}:", ctx.input_user);
+    let mut found = report(ctx.for_input_user(&svd.recommend(&ctx.interactions, &ctx.input_user, &ctx.blocklist, &ctx.ranker, 3), Method::PureSvd, ScoreType::ModelScore));
+    if let Some(song_id) = &ctx.input_song_id {
+        println!("Similar songs (PureSVD) to '{}':", ctx.input_song);
+        let ranked = svd.similar_songs(&ctx.interactions, song_id, &ctx.blocklist, &ctx.ranker, 3);
+        found.extend(report(ctx.for_input_song(&ranked, Method::PureSvd, ScoreType::Similarity)));
+    }
+    found
+}
+
+//random walk with restart on the user-song graph, seeded from the input song and the input user
+fn demo_random_walk(ctx: &Demo) -> Vec<Recommendation> {
+    let mut walk_seeds = vec![Seed::User(ctx.input_user.clone())];
+    if let Some(song_id) = &ctx.input_song_id {
+        walk_seeds.push(Seed::Song(song_id.clone()));
+    }
+    println!("Random walk recommendations for user {} and '{}':", ctx.input_user, ctx.input_song);
+    let ranked = random_walk_with_restart(&ctx.interactions, &walk_seeds, &WalkConfig::default(), &ctx.blocklist, &ctx.ranker, 3);
+    let walk_songs: Vec<String> = ctx.history.iter().chain(&ctx.input_seeds).cloned().collect();
+    report(ctx.recommend(&ranked, Method::RandomWalk, ScoreType::Probability, None, &walk_songs, 2))
+}
+
+//node2vec song embeddings, nearest neighbours are songs that "sound like" the input song to listeners,
+//then an approximate nearest neighbour index over the same vectors for fast "sounds like" queries
+fn demo_song_vectors(ctx: &Demo) -> Vec<Recommendation> {
+    let embeddings = train_node2vec(&ctx.interactions, &Node2VecConfig::default());
+    let mut found = Vec::new();
+    if let Some(song_id) = &ctx.input_song_id {
+        println!("Sounds like '{}' (node2vec):", ctx.input_song);
+        found.extend(report(ctx.for_input_song(&embeddings.nearest(song_id, &ctx.blocklist, &ctx.ranker, 3), Method::Node2Vec, ScoreType::Similarity)));
+    }
+    if embeddings.save_word2vec("song_vectors.txt").is_err() {
+        eprintln!("Problem saving song vectors");
+    }
+
+    //a saved index is reused if it was built from these vectors with this config, otherwise it is built and saved for next time
+    let hnsw_config = HnswConfig::default();
+    let index = match HnswIndex::load("song_index.hnsw") {
+        Ok(mut saved) if saved.matches(&embeddings, &hnsw_config) => {
+            saved.config.ef_search = hnsw_config.ef_search;
+            saved
+        }
+        saved => {
+            if saved.is_ok() {
+                println!("Saved song index is out of date, rebuilding it");
+            }
+            let built = HnswIndex::build(&embeddings, &hnsw_config);
+            if built.save("song_index.hnsw").is_err() {
+                eprintln!("Problem saving song index");
+            }
+            built
+        }
+    };
+    println!("HNSW recall@10 against exact search: {:.3}", index.recall(10, 100));
+    if let Some(song_id) = &ctx.input_song_id {
+        println!("Sounds like '{}' (HNSW):", ctx.input_song);
+        found.extend(report(ctx.for_input_song(&index.nearest(song_id, &ctx.blocklist, &ctx.ranker, 3), Method::Hnsw, ScoreType::Similarity)));
     }
+    found
 }
 
+//MinHash LSH, pairs of songs with nearly the same listeners found without comparing every pair
+fn demo_minhash(ctx: &Demo) {
+    let config = MinHashConfig::default();
+    println!("MinHash finds a pair with Jaccard 0.5 with probability {:.3}", config.candidate_probability(0.5));
+    for (a, b, similarity) in similar_song_pairs(ctx.graph, &config).iter().take(5) {
+        println!("Similar listener sets: '{}' and '{}' with estimated Jaccard {:.2}", ctx.catalog.title(a), ctx.catalog.title(b), similarity);
+    }
+}
+
+//function to look up the song_ids for titles typed in, through their post-dedup titles (titles not in the data are left out)
+fn typed_song_ids(titles: &[&str], ctx: &Demo) -> Vec<String> {
+    titles.iter().filter_map(|title| resolve_title(title, None, ctx.data)).filter_map(|title| ctx.catalog.song_id(&title).cloned()).collect()
+}
+
+//association rules, every user's songs (by song_id, so the blocklist applies) is one basket
+fn demo_association_rules(ctx: &Demo) -> Vec<Recommendation> {
+    let mined = mine_rules(&ctx.graph.user_songs, &RuleConfig::default());
+    println!("Found {} frequent song sets and {} association rules", mined.sets.len(), mined.rules.len());
+    for set in mined.sets.iter().filter(|set| set.songs.len() > 1).take(3) {
+        let set_titles: Vec<&str> = set.songs.iter().map(|song_id| ctx.catalog.title(song_id)).collect();
+        println!("Played together: {:?} by {} users (support {:.3})", set_titles, set.count, set.support);
+    }
+    if mined.save_rules("association_rules.csv").is_err() {
+        eprintln!("Problem saving association rules");
+    }
+    let played = ["Imagine", "Yesterday"];
+    println!("If you played {:?}, play:", played);
+    let played_ids = typed_song_ids(&played, ctx);
+    report(ctx.recommend(&mined.recommend(&played_ids, &ctx.blocklist, &ctx.ranker, 3), Method::AssociationRule, ScoreType::Probability, None, &played_ids, 2))
+}
+
+//playlist recommendations from several seed songs at once
+fn demo_playlist(ctx: &Demo) -> Vec<Recommendation> {
+    let playlist = typed_song_ids(&["Imagine", "Yesterday", "Let It Be"], ctx);
+    let mut found = Vec::new();
+    for aggregation in [SeedAggregation::Union, SeedAggregation::IntersectionWeighted, SeedAggregation::PerSeedAverage(Similarity::Cosine)] {
+        let score_type = if let SeedAggregation::PerSeedAverage(_) = aggregation { ScoreType::Similarity } else { ScoreType::WeightedCount };
+        println!("Playlist recommendations ({:?}):", aggregation);
+        let ranked = recommend_from_songs(&playlist, ctx.graph, aggregation, &ctx.blocklist, &ctx.ranker, 1);
+        found.extend(report(ctx.recommend(&ranked, Method::Playlist, score_type, None, &playlist, 3)));
+    }
+    found
+}
+
+//artist level recommendations (artist can be a name or an artist_id)
+fn demo_artists(ctx: &Demo) -> Vec<Recommendation> {
+    let artist_id = match find_artist_id(ctx.input_artist, ctx.data) {
+        Some(artist_id) => artist_id,
+        None => {
+            println!("Artist '{}' not found", ctx.input_artist);
+            return Vec::new();
+        }
+    };
+    let names = artist_names(ctx.data);
+    for (similar, shared) in similar_artists(&artist_id, ctx.data, 5) {
+        println!("Similar artist to '{}' is '{}' with {} shared listeners", ctx.input_artist, names[&similar], shared);
+    }
+    println!("Recommended songs for fans of '{}':", ctx.input_artist);
+    let artist_songs: Vec<String> = ctx.data.iter().filter(|record| record.artist_id == artist_id).map(|record| record.song_id.clone()).collect::<HashSet<String>>().into_iter().collect();
+    let ranked = recommend_from_artist(&artist_id, ctx.data, true, &ctx.blocklist, &ctx.ranker, 5);
+    report(ctx.recommend(&ranked, Method::ArtistSeed, ScoreType::WeightedCount, None, &artist_songs, 2))
+}
 
 fn main() {
     let file = match File::open("src/merged_data.csv") {
         Ok(open_file) => open_file,
-        Err(failed) => {
+        Err(_failed) => {
             eprintln!("Problem opening file");
             return; //stop code from running if error
         }
@@ -134,32 +606,47 @@ fn main() {
 
     let data = match read_msd(&file) {
         Ok(create_data) => create_data,
-        Err(failed) => {
+        Err(_failed) => {
             eprintln!("Problem reading MSD");
             return;
         }
     };
+    let data = merge_duplicates(data); //collapse duplicate tracks into one canonical song before counting listeners
+    let catalog = SongCatalog::new(&data);
+    let graph = ListenGraph::new(&data);
+    let ctx = Demo::new(&data, &catalog, &graph, "Imagine", "John Lennon"); //The Foundation for <=5 Imagine for >5
 
-    let input_song = "Imagine"; //The Foundation for <=5 Imagine for >5
-    
-    let users = songs_to_users(input_song, &data);
+    let mut all: Vec<Recommendation> = Vec::new(); //every recommendation made below, written out as JSON at the end
+    all.extend(demo_most_popular(&ctx));
+    all.extend(demo_find_more_songs(&ctx));
+    all.extend(demo_cold_start(&ctx));
+    all.extend(demo_weighted(&ctx));
+    all.extend(demo_similarity(&ctx));
+    all.extend(demo_expansion(&ctx));
+    all.extend(demo_long_tail(&ctx));
+    all.extend(demo_tie_breaks(&ctx));
+    all.extend(demo_user_knn(&ctx));
+    all.extend(demo_new_to_user(&ctx));
+    all.extend(demo_item_item(&ctx));
+    all.extend(demo_als(&ctx));
+    all.extend(demo_bpr(&ctx));
+    all.extend(demo_ease(&ctx));
+    all.extend(demo_svd(&ctx));
+    all.extend(demo_random_walk(&ctx));
+    all.extend(demo_song_vectors(&ctx));
+    demo_minhash(&ctx);
+    all.extend(demo_association_rules(&ctx));
+    all.extend(demo_playlist(&ctx));
+    all.extend(demo_artists(&ctx));
 
-    //printing fn most_popular (only works if more than 5 users)
-    if users.len() > 5 { 
-        let user_songs_hm = users_to_songs(&users, &data);
-        if let Some((song, count)) = most_popular_song(&user_songs_hm, input_song) {
-            println!("Recommended song for '{}' is '{}' with {} listens", input_song, song, count);
+    //every recommendation above as JSON for downstream consumers
+    match to_json(&all) {
+        Ok(json) => {
+            if std::fs::write("recommendations.json", json).is_err() {
+                eprintln!("Problem writing recommendations.json");
+            }
         }
-    } else {
-        println!("Song is not popular, looking for better recommendations...");
-    }
-
-    //prints fn find_more_songs (<5 users)
-    match find_more_songs(input_song, &data) {
-        Some((song, count)) => {
-            println!("Most popular recommended song is {} with {} listeners", song, count);
-        },
-        None => {}
+        Err(_failed) => eprintln!("Problem converting recommendations to JSON"),
     }
 }
 
@@ -170,12 +657,12 @@ mod tests {
 
     fn fake_data() -> Vec<MSD> {
         vec![
-            //0's represent unimportant data
+            //0's represent unimportant data, each song has its own song_id so exclusions can tell them apart
             MSD { 
                 //user1 listens to Song A
                 unknown: "0".to_string(),
                 user_id: "user1".to_string(),
-                song_id: "0".to_string(),
+                song_id: "SA".to_string(),
                 listen_count: "0".to_string(),
                 track_id: "0".to_string(),
                 artist_id: "0".to_string(),
@@ -186,7 +673,7 @@ mod tests {
                 //user2 listens to Song A
                 unknown: "0".to_string(),
                 user_id: "user2".to_string(),
-                song_id: "0".to_string(),
+                song_id: "SA".to_string(),
                 listen_count: "0".to_string(),
                 track_id: "0".to_string(),
                 artist_id: "0".to_string(),
@@ -197,7 +684,7 @@ mod tests {
                 //user1 listens to Song B
                 unknown: "0".to_string(),
                 user_id: "user1".to_string(),
-                song_id: "0".to_string(),
+                song_id: "SB".to_string(),
                 listen_count: "0".to_string(),
                 track_id: "0".to_string(),
                 artist_id: "0".to_string(),
@@ -208,7 +695,7 @@ mod tests {
                 //user3 listens to Song C
                 unknown: "0".to_string(),
                 user_id: "user3".to_string(),
-                song_id: "0".to_string(),
+                song_id: "SC".to_string(),
                 listen_count: "0".to_string(),
                 track_id: "0".to_string(),
                 artist_id: "0".to_string(),
@@ -257,15 +744,60 @@ mod tests {
         let data = fake_data();
         let users: HashSet<String> = ["user1".to_string(), "user2".to_string()].into_iter().collect();
         let user_songs_hm = users_to_songs(&users, &data);
-        let most_popular = most_popular_song(&user_songs_hm, "Song A");
+        let catalog = SongCatalog::new(&data);
+        let ranker = Ranker::new(TieBreak::Title, &catalog);
+        let most_popular = most_popular_song(&user_songs_hm, &Exclusions::new().with_titles(["Song A"], &catalog), &catalog, &ranker);
         //two people listen to Song A, most popular outside of that is Song B with 1 play
         assert_eq!(most_popular, Some(("Song B".to_string(), 1)));
     }
     #[test]
+    fn test_most_popular_song_tie_break() {
+        use crate::test_data::record;
+        //user1 played all three songs once, so they tie; Mango has a second listener in the whole data
+        let data = vec![
+            record("user1", "S1", "A1", "Zebra", 1),
+            record("user1", "S2", "A1", "Apple", 1),
+            record("user1", "S3", "A1", "Mango", 1),
+            record("user2", "S3", "A1", "Mango", 1),
+        ];
+        let users: HashSet<String> = ["user1".to_string()].into_iter().collect();
+        let user_songs_hm = users_to_songs(&users, &data);
+        let catalog = SongCatalog::new(&data);
+        let winner = |tie_break| most_popular_song(&user_songs_hm, &Exclusions::new(), &catalog, &Ranker::new(tie_break, &catalog)).unwrap().0;
+        assert_eq!(winner(TieBreak::SongId), "Zebra");
+        assert_eq!(winner(TieBreak::Title), "Apple");
+        assert_eq!(winner(TieBreak::Popularity), "Mango");
+    }
+    #[test]
     fn test_find_more_songs() {
         let data = fake_data();
-        let actual = find_more_songs("Song B", &data);
-        assert_eq!(actual, Some(("Song A".to_string(), 2))); //Song A should be the most popular with 2 users
+        let catalog = SongCatalog::new(&data);
+        let (song, count, explanation) = find_more_songs("Song B", &data, &ListenGraph::new(&data), &catalog, &Ranker::new(TieBreak::Title, &catalog)).unwrap();
+        assert_eq!((song, count), ("Song A".to_string(), 2)); //Song A should be the most popular with 2 users
+        //Song A is the only bridge, and a song is never its own bridge
+        assert!(explanation.because_of.is_empty());
+    }
+    #[test]
+    fn test_explain_find_more_songs() {
+        use crate::test_data::record;
+        //Seed's listeners lead to Bridge 1 (2 of them) and Bridge 2 (1), whose listeners all played Target
+        let mut data = vec![
+            record("user1", "S0", "A0", "Seed", 1),
+            record("user1", "S1", "A1", "Bridge 1", 1),
+            record("user1", "S2", "A2", "Bridge 2", 1),
+            record("user2", "S0", "A0", "Seed", 1),
+            record("user2", "S1", "A1", "Bridge 1", 1),
+        ];
+        for (user, bridge) in [("user3", "1"), ("user7", "1"), ("user4", "2"), ("user6", "2"), ("user8", "2")] {
+            data.push(record(user, &format!("S{}", bridge), &format!("A{}", bridge), &format!("Bridge {}", bridge), 1));
+            data.push(record(user, "ST", "AT", "Target", 1));
+        }
+        let catalog = SongCatalog::new(&data);
+        let (song, count, explanation) = find_more_songs("Seed", &data, &ListenGraph::new(&data), &catalog, &Ranker::new(TieBreak::Title, &catalog)).unwrap();
+        assert_eq!((song.as_str(), count), ("Target", 5));
+        let because_of: Vec<(&str, &str, usize)> = explanation.because_of.iter().map(|evidence| (evidence.song_id.as_str(), evidence.title.as_str(), evidence.shared_listeners)).collect();
+        assert_eq!(because_of, vec![("S2", "Bridge 2", 3), ("S1", "Bridge 1", 2)]);
+        assert_eq!(explanation.shared_listeners, 5);
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,154 @@
+use crate::listen_graph::ListenGraph;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+//settings for MinHash signatures with LSH banding
+//a pair of songs with Jaccard similarity s becomes a candidate with probability 1 - (1 - s^rows_per_band)^bands,
+//so more bands raises recall and more rows per band raises precision
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinHashConfig {
+    pub bands: usize,
+    pub rows_per_band: usize,
+    pub threshold: f64,         //candidates whose estimated Jaccard is below this are dropped
+    pub max_bucket_size: usize, //buckets bigger than this are skipped so one huge bucket can not make the pairing quadratic
+    pub seed: u64,
+}
+
+impl Default for MinHashConfig {
+    fn default() -> MinHashConfig {
+        MinHashConfig { bands: 20, rows_per_band: 5, threshold: 0.5, max_bucket_size: 1000, seed: 42 }
+    }
+}
+
+impl MinHashConfig {
+    //chance that a pair with Jaccard similarity s lands in the same bucket in at least one band
+    pub fn candidate_probability(&self, similarity: f64) -> f64 {
+        1.0 - (1.0 - similarity.powi(self.rows_per_band as i32)).powi(self.bands as i32)
+    }
+}
+
+//FNV-1a, a fixed hash for user_ids so signatures are the same on every run and machine
+fn fnv1a(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+//one of the signature's hash functions: mixes the user's hash with a per-function seed (SplitMix64 finalizer)
+fn mix(value: u64, seed: u64) -> u64 {
+    let mut z = value ^ seed;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+//function to make a MinHash signature for every song from its set of listeners
+//each entry is the smallest hash of any listener under one hash function
+pub fn song_signatures(graph: &ListenGraph, config: &MinHashConfig) -> HashMap<String, Vec<u64>> {
+    let length = config.bands * config.rows_per_band;
+    let seeds: Vec<u64> = (0..length as u64).map(|i| mix(i, config.seed)).collect();
+
+    let user_hashes: HashMap<&String, u64> = graph.user_songs.keys().map(|user| (user, fnv1a(user))).collect();
+    let mut signatures = HashMap::new();
+    for (song, users) in &graph.song_users {
+        let mut signature = vec![u64::MAX; length];
+        for user in users {
+            let user_hash = user_hashes[user];
+            for (slot, seed) in signature.iter_mut().zip(&seeds) {
+                *slot = (*slot).min(mix(user_hash, *seed));
+            }
+        }
+        signatures.insert(song.clone(), signature);
+    }
+    signatures
+}
+
+//estimated Jaccard similarity: the share of signature entries that match
+pub fn estimated_jaccard(a: &[u64], b: &[u64]) -> f64 {
+    if a.is_empty() {
+        return 0.0;
+    }
+    a.iter().zip(b).filter(|(x, y)| x == y).count() as f64 / a.len() as f64
+}
+
+//function to find pairs of songs with highly similar listener sets without comparing every pair
+//songs are bucketed by each band of their signature, only songs sharing a bucket are compared
+//returns (song_id, song_id, estimated Jaccard) with the smaller song_id first, most similar pairs first
+pub fn similar_song_pairs(graph: &ListenGraph, config: &MinHashConfig) -> Vec<(String, String, f64)> {
+    let signatures = song_signatures(graph, config);
+    let mut songs: Vec<&String> = signatures.keys().collect();
+    songs.sort(); //fixed order so buckets and pairs always come out the same
+
+    let mut candidates: HashSet<(&String, &String)> = HashSet::new();
+    for band in 0..config.bands {
+        let start = band * config.rows_per_band;
+        let end = start + config.rows_per_band;
+        let mut buckets: HashMap<&[u64], Vec<&String>> = HashMap::new();
+        for song in &songs {
+            buckets.entry(&signatures[*song][start..end]).or_default().push(song);
+        }
+        for bucket in buckets.values() {
+            if bucket.len() < 2 || bucket.len() > config.max_bucket_size {
+                continue;
+            }
+            for (i, a) in bucket.iter().enumerate() {
+                for b in &bucket[i + 1..] {
+                    candidates.insert((a, b)); //bucket is in sorted order so a < b
+                }
+            }
+        }
+    }
+
+    let mut pairs: Vec<(String, String, f64)> = candidates
+        .into_iter()
+        .map(|(a, b)| (a.clone(), b.clone(), estimated_jaccard(&signatures[a], &signatures[b])))
+        .filter(|(_, _, similarity)| *similarity >= config.threshold)
+        .collect();
+    pairs.sort_by(|x, y| y.2.partial_cmp(&x.2).unwrap_or(Ordering::Equal).then_with(|| x.0.cmp(&y.0)).then_with(|| x.1.cmp(&y.1)));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::MSD;
+    use crate::test_data::record;
+
+    //S1 and S2 share 9 of 10 listeners, S3 has a completely different audience
+    fn fake_data() -> Vec<MSD> {
+        let mut data = Vec::new();
+        for user in 0..10 {
+            data.push(record(&format!("user{}", user), "S1", "A1", "Song 1", 1));
+            if user > 0 {
+                data.push(record(&format!("user{}", user), "S2", "A1", "Song 2", 1));
+            }
+            data.push(record(&format!("other{}", user), "S3", "A2", "Song 3", 1));
+        }
+        data
+    }
+
+    #[test]
+    fn test_estimated_jaccard() {
+        let graph = ListenGraph::new(&fake_data());
+        let config = MinHashConfig { bands: 50, rows_per_band: 4, ..MinHashConfig::default() };
+        let signatures = song_signatures(&graph, &config);
+        //true Jaccard of S1 and S2 is 0.9
+        assert!((estimated_jaccard(&signatures["S1"], &signatures["S2"]) - 0.9).abs() < 0.1);
+        assert_eq!(estimated_jaccard(&signatures["S1"], &signatures["S3"]), 0.0);
+    }
+
+    #[test]
+    fn test_similar_song_pairs() {
+        let graph = ListenGraph::new(&fake_data());
+        let pairs = similar_song_pairs(&graph, &MinHashConfig::default());
+        assert_eq!(pairs.len(), 1);
+        assert_eq!((pairs[0].0.as_str(), pairs[0].1.as_str()), ("S1", "S2"));
+
+        let config = MinHashConfig::default();
+        assert!(config.candidate_probability(0.9) > 0.99);
+        assert!(config.candidate_probability(0.1) < 0.01);
+    }
+}
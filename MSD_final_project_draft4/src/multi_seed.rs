@@ -0,0 +1,102 @@
+use crate::exclusions::Exclusions;
+use crate::listen_graph::ListenGraph;
+use crate::scoring::{top_scores, Ranker};
+use crate::similarity::{song_similarities, Similarity};
+use std::collections::{HashMap, HashSet};
+
+//how the listeners of several seed songs (a playlist) are combined
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SeedAggregation {
+    Union,                      //everyone who played any seed counts once, same as most_popular_song over the pooled listeners
+    IntersectionWeighted,       //a listener counts by the share of the seeds they played, so fans of the whole playlist count most
+    PerSeedAverage(Similarity), //each seed is scored on its own with the similarity measure and the scores are averaged
+}
+
+//function to recommend songs for a playlist of seed song_ids, none of the seeds (or excluded songs) are returned
+//seeds that are not in the data are ignored. Returns up to k (song_id, score) pairs, best first.
+pub fn recommend_from_songs(seeds: &[String], graph: &ListenGraph, aggregation: SeedAggregation, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+    let seed_set: HashSet<&String> = seeds.iter().filter(|seed| graph.song_users.contains_key(*seed)).collect();
+    let mut seeds_in_order: Vec<&String> = seed_set.iter().copied().collect();
+    seeds_in_order.sort(); //scores are added up seed by seed and user by user in a fixed order so runs match exactly
+    if seed_set.is_empty() {
+        return Vec::new();
+    }
+
+    let mut song_score: HashMap<String, f64> = HashMap::new();
+    match aggregation {
+        SeedAggregation::Union | SeedAggregation::IntersectionWeighted => {
+            //how many of the seeds each listener played
+            let mut seeds_played: HashMap<&String, usize> = HashMap::new();
+            for seed in &seeds_in_order {
+                for user in &graph.song_users[*seed] {
+                    *seeds_played.entry(user).or_insert(0) += 1;
+                }
+            }
+            let mut listeners: Vec<(&String, usize)> = seeds_played.into_iter().collect();
+            listeners.sort();
+            for (user, played) in listeners {
+                let weight = match aggregation {
+                    SeedAggregation::Union => 1.0,
+                    _ => played as f64 / seed_set.len() as f64,
+                };
+                for song in &graph.user_songs[user] {
+                    if !seed_set.contains(song) {
+                        *song_score.entry(song.clone()).or_insert(0.0) += weight;
+                    }
+                }
+            }
+        }
+        SeedAggregation::PerSeedAverage(similarity) => {
+            for seed in &seeds_in_order {
+                for (song, score) in song_similarities(seed, graph, similarity) {
+                    if !seed_set.contains(&song) {
+                        *song_score.entry(song).or_insert(0.0) += score / seed_set.len() as f64;
+                    }
+                }
+            }
+        }
+    }
+    top_scores(exclusions.filter(song_score), ranker, k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::record;
+
+    //user1 played both seeds S1 and S2, user2 and user3 played only S1
+    fn fake_graph() -> ListenGraph {
+        ListenGraph::new(&[
+            record("user1", "S1", "A1", "Seed 1", 1),
+            record("user1", "S2", "A1", "Seed 2", 1),
+            record("user1", "S3", "A2", "Both Seeds Fan Song", 1),
+            record("user2", "S1", "A1", "Seed 1", 1),
+            record("user2", "S4", "A3", "Seed 1 Fan Song", 1),
+            record("user3", "S1", "A1", "Seed 1", 1),
+            record("user3", "S4", "A3", "Seed 1 Fan Song", 1),
+        ])
+    }
+
+    #[test]
+    fn test_aggregations() {
+        let graph = fake_graph();
+        let seeds = vec!["S1".to_string(), "S2".to_string(), "missing".to_string()];
+
+        //union: S4 has two listeners against S3's one
+        let union = recommend_from_songs(&seeds, &graph, SeedAggregation::Union, &Exclusions::new(), &Ranker::default(), 10);
+        assert_eq!(union, vec![("S4".to_string(), 2.0), ("S3".to_string(), 1.0)]);
+
+        //intersection weighted: user1 counts 1.0, user2 and user3 count 0.5 each, so it is a tie broken by song_id
+        let weighted = recommend_from_songs(&seeds, &graph, SeedAggregation::IntersectionWeighted, &Exclusions::new(), &Ranker::default(), 10);
+        assert_eq!(weighted, vec![("S3".to_string(), 1.0), ("S4".to_string(), 1.0)]);
+
+        //per seed average: S3 is similar to both seeds, S4 only to S1
+        let average = recommend_from_songs(&seeds, &graph, SeedAggregation::PerSeedAverage(Similarity::Jaccard), &Exclusions::new(), &Ranker::default(), 10);
+        assert_eq!(average[0].0, "S3");
+        //no seed ever comes back
+        assert!(average.iter().all(|(song, _)| song != "S1" && song != "S2"));
+        //excluded songs are dropped before the top k is taken
+        let excluded = recommend_from_songs(&seeds, &graph, SeedAggregation::Union, &Exclusions::new().with_songs(["S4"]), &Ranker::default(), 1);
+        assert_eq!(excluded, vec![("S3".to_string(), 1.0)]);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::exclusions::Exclusions;
+use crate::interactions::Interactions;
+use crate::linalg::{cosine, dot};
+use crate::rng::Rng;
+use crate::scoring::{top_scores, Ranker};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+//settings for node2vec (Grover and Leskovec) on the song co-listen graph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Node2VecConfig {
+    pub dimensions: usize,     //length of each song vector
+    pub max_neighbours: usize, //each song keeps only its strongest co-listen edges, keeps the graph small on the full data
+    pub walks_per_song: usize,
+    pub walk_length: usize,
+    pub p: f64,                //return parameter, high p makes walks less likely to step straight back
+    pub q: f64,                //in-out parameter, q < 1 explores outwards (DFS-like), q > 1 stays local (BFS-like)
+    pub window: usize,         //songs this many steps apart in a walk count as context for each other
+    pub negatives: usize,      //negative samples per (song, context) pair
+    pub epochs: usize,
+    pub learning_rate: f64,    //starting learning rate, decays linearly to near 0
+    pub seed: u64,
+}
+
+impl Default for Node2VecConfig {
+    fn default() -> Node2VecConfig {
+        Node2VecConfig {
+            dimensions: 64,
+            max_neighbours: 50,
+            walks_per_song: 10,
+            walk_length: 40,
+            p: 1.0,
+            q: 1.0,
+            window: 5,
+            negatives: 5,
+            epochs: 1,
+            learning_rate: 0.025,
+            seed: 42,
+        }
+    }
+}
+
+//learned song vectors, one per song_id
+pub struct SongEmbeddings {
+    pub song_ids: Vec<String>,
+    pub song_index: HashMap<String, usize>,
+    pub vectors: Vec<Vec<f64>>,
+}
+
+//song co-listen graph: for each song, (neighbour song, shared listeners) sorted by neighbour
+fn co_listen_graph(interactions: &Interactions, max_neighbours: usize) -> Vec<Vec<(usize, f64)>> {
+    let mut graph = Vec::with_capacity(interactions.num_songs());
+    for (song, listeners) in interactions.by_song.iter().enumerate() {
+        let mut counts: HashMap<usize, f64> = HashMap::new();
+        for &(user, _) in listeners {
+            for &(other, _) in &interactions.by_user[user] {
+                if other != song { //no self loops
+                    *counts.entry(other).or_insert(0.0) += 1.0;
+                }
+            }
+        }
+        let mut edges: Vec<(usize, f64)> = counts.into_iter().collect();
+        edges.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0))); //strongest first, ties by song number
+        edges.truncate(max_neighbours);
+        edges.sort_by_key(|&(other, _)| other); //sorted by neighbour so "is t next to x" is a binary search
+        graph.push(edges);
+    }
+    graph
+}
+
+//picks an index with probability proportional to its weight
+fn pick(weights: &[f64], rng: &mut Rng) -> usize {
+    let total: f64 = weights.iter().sum();
+    let mut target = rng.next_f64() * total;
+    for (i, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return i;
+        }
+        target -= weight;
+    }
+    weights.len() - 1
+}
+
+//one biased walk starting at the song
+//the step after t -> v to x is weighted by the edge weight times 1/p if x is t, 1 if x is next to t, and 1/q otherwise
+fn biased_walk(graph: &[Vec<(usize, f64)>], start: usize, config: &Node2VecConfig, rng: &mut Rng) -> Vec<usize> {
+    let mut walk = vec![start];
+    while walk.len() < config.walk_length {
+        let current = walk[walk.len() - 1];
+        let edges = &graph[current];
+        if edges.is_empty() {
+            break;
+        }
+        let next = if walk.len() == 1 {
+            let weights: Vec<f64> = edges.iter().map(|&(_, weight)| weight).collect();
+            edges[pick(&weights, rng)].0
+        } else {
+            let previous = walk[walk.len() - 2];
+            let weights: Vec<f64> = edges
+                .iter()
+                .map(|&(x, weight)| {
+                    if x == previous {
+                        weight / config.p
+                    } else if graph[previous].binary_search_by_key(&x, |&(other, _)| other).is_ok() {
+                        weight
+                    } else {
+                        weight / config.q
+                    }
+                })
+                .collect();
+            edges[pick(&weights, rng)].0
+        };
+        walk.push(next);
+    }
+    walk
+}
+
+//one skip-gram with negative sampling update for a (song, context or negative song, label) pair
+fn sgns_step(input: &[f64], output: &mut [f64], label: f64, learning_rate: f64, gradient: &mut [f64]) {
+    let prediction = 1.0 / (1.0 + (-dot(input, output)).exp());
+    let g = learning_rate * (label - prediction);
+    for ((grad, out), inp) in gradient.iter_mut().zip(output.iter_mut()).zip(input.iter()) {
+        *grad += g * *out;
+        *out += g * inp;
+    }
+}
+
+//function to train node2vec song vectors: biased random walks on the co-listen graph, then skip-gram with negative sampling
+//the same data, config and seed always give the same vectors
+pub fn train_node2vec(interactions: &Interactions, config: &Node2VecConfig) -> SongEmbeddings {
+    let mut rng = Rng::new(config.seed);
+    let graph = co_listen_graph(interactions, config.max_neighbours);
+    let num_songs = interactions.num_songs();
+
+    let mut walks = Vec::new();
+    for _ in 0..config.walks_per_song {
+        for song in 0..num_songs {
+            walks.push(biased_walk(&graph, song, config, &mut rng));
+        }
+    }
+
+    //negatives are drawn from how often songs show up in walks, to the 3/4 power like word2vec
+    let mut frequency: Vec<f64> = vec![0.0; num_songs];
+    for walk in &walks {
+        for &song in walk {
+            frequency[song] += 1.0;
+        }
+    }
+    let mut cumulative = Vec::with_capacity(num_songs);
+    let mut total = 0.0;
+    for count in &frequency {
+        total += count.powf(0.75);
+        cumulative.push(total);
+    }
+
+    let dimensions = config.dimensions;
+    let mut input: Vec<Vec<f64>> = (0..num_songs).map(|_| (0..dimensions).map(|_| (rng.next_f64() - 0.5) / dimensions as f64).collect()).collect();
+    let mut output: Vec<Vec<f64>> = vec![vec![0.0; dimensions]; num_songs];
+
+    let total_steps = (config.epochs * walks.len()).max(1) as f64;
+    let mut step = 0.0;
+    for _ in 0..config.epochs {
+        for walk in &walks {
+            let learning_rate = (config.learning_rate * (1.0 - step / total_steps)).max(config.learning_rate * 1e-4);
+            step += 1.0;
+            for (position, &center) in walk.iter().enumerate() {
+                let start = position.saturating_sub(config.window);
+                let end = (position + config.window + 1).min(walk.len());
+                for (context_position, &context) in walk.iter().enumerate().take(end).skip(start) {
+                    if context_position == position {
+                        continue;
+                    }
+                    let mut gradient = vec![0.0; dimensions];
+                    sgns_step(&input[center], &mut output[context], 1.0, learning_rate, &mut gradient);
+                    for _ in 0..config.negatives {
+                        let target = rng.next_f64() * total;
+                        let negative = cumulative.partition_point(|&running| running <= target).min(num_songs - 1);
+                        if negative != context {
+                            sgns_step(&input[center], &mut output[negative], 0.0, learning_rate, &mut gradient);
+                        }
+                    }
+                    for (value, grad) in input[center].iter_mut().zip(&gradient) {
+                        *value += grad;
+                    }
+                }
+            }
+        }
+    }
+
+    SongEmbeddings { song_ids: interactions.song_ids.clone(), song_index: interactions.song_index.clone(), vectors: input }
+}
+
+impl SongEmbeddings {
+    //function to find the k songs whose vectors are closest (cosine) to the seed song's, the "sounds like" query
+    pub fn nearest(&self, song_id: &str, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+        let seed = match self.song_index.get(song_id) {
+            Some(&seed) => seed,
+            None => return Vec::new(),
+        };
+        let mut song_score: HashMap<String, f64> = HashMap::new();
+        for (song, vector) in self.vectors.iter().enumerate() {
+            if song != seed && !exclusions.excludes_song(&self.song_ids[song]) {
+                song_score.insert(self.song_ids[song].clone(), cosine(&self.vectors[seed], vector));
+            }
+        }
+        top_scores(song_score, ranker, k)
+    }
+
+    //function to save the vectors in word2vec text format: a "count dimensions" line, then "song_id v1 v2 ..." per song
+    pub fn save_word2vec(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        let dimensions = self.vectors.first().map_or(0, |vector| vector.len());
+        writeln!(writer, "{} {}", self.song_ids.len(), dimensions)?;
+        for (song_id, vector) in self.song_ids.iter().zip(&self.vectors) {
+            let values: Vec<String> = vector.iter().map(|value| format!("{:.6}", value)).collect();
+            writeln!(writer, "{} {}", song_id, values.join(" "))?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exclusions::Exclusions;
+    use crate::csv_reader::MSD;
+    use crate::test_data::record;
+
+    //two groups of songs that are only ever played together within the group
+    fn fake_data() -> Vec<MSD> {
+        let mut data = Vec::new();
+        for group in ["A", "B"] {
+            for user in 0..4 {
+                for song in 0..4 {
+                    let song_id = format!("{}{}", group, song);
+                    data.push(record(&format!("user{}{}", group, user), &song_id, group, &song_id, 1));
+                }
+            }
+        }
+        data
+    }
+
+    fn small_config() -> Node2VecConfig {
+        Node2VecConfig { dimensions: 8, walks_per_song: 20, walk_length: 10, window: 2, epochs: 3, ..Node2VecConfig::default() }
+    }
+
+    #[test]
+    fn test_node2vec_nearest() {
+        let interactions = Interactions::new(&fake_data());
+        let embeddings = train_node2vec(&interactions, &small_config());
+        let nearest = embeddings.nearest("A0", &Exclusions::new(), &Ranker::default(), 3);
+        assert!(nearest.iter().all(|(song, _)| song.starts_with('A')), "{:?}", nearest);
+        //the nearest song excluded, the other two move up
+        let without_first = embeddings.nearest("A0", &Exclusions::new().with_songs([nearest[0].0.clone()]), &Ranker::default(), 2);
+        assert_eq!(without_first, nearest[1..].to_vec());
+        //same seed, same vectors
+        assert_eq!(embeddings.vectors, train_node2vec(&interactions, &small_config()).vectors);
+    }
+
+    #[test]
+    fn test_save_word2vec() {
+        let interactions = Interactions::new(&fake_data());
+        let embeddings = train_node2vec(&interactions, &Node2VecConfig { dimensions: 3, walks_per_song: 1, walk_length: 3, ..Node2VecConfig::default() });
+        let path = std::env::temp_dir().join("node2vec_test.txt");
+        embeddings.save_word2vec(path.to_str().unwrap()).unwrap();
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = saved.lines().collect();
+        assert_eq!(lines[0], "8 3");
+        assert_eq!(lines.len(), 9);
+        assert!(lines[1].starts_with("A0 "));
+        assert_eq!(lines[1].split(' ').count(), 4);
+    }
+}
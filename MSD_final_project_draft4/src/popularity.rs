@@ -0,0 +1,120 @@
+use crate::listen_graph::ListenGraph;
+use crate::recommendation::Reranker;
+use crate::scoring::{top_scores, Ranker};
+use std::collections::HashMap;
+
+//ways to push a ranked list away from the chart hits and towards the long tail
+//every recommender returns (song_id, score) pairs best first, so these work on the output of any of them
+#[derive(Debug, Clone, PartialEq)]
+pub enum Debiasing {
+    InversePopularity(f64), //score / listeners^beta, beta = 0 changes nothing and beta = 1 divides the popularity all the way out
+    BucketQuotas(Vec<usize>), //songs are split into equal sized popularity buckets (head first), at most quota[i] songs from bucket i
+    MaxGlobalRank(usize),   //songs inside the global top n are dropped, so nothing more popular than rank n + 1 comes back
+}
+
+impl Debiasing {
+    //the label put on recommendations this method reordered
+    pub fn reranker(&self) -> Reranker {
+        match self {
+            Debiasing::InversePopularity(_) => Reranker::InversePopularity,
+            Debiasing::BucketQuotas(_) => Reranker::BucketQuotas,
+            Debiasing::MaxGlobalRank(_) => Reranker::MaxGlobalRank,
+        }
+    }
+}
+
+//function to rank every song by its number of listeners, 1 = most listened to, ties by song_id
+pub fn global_ranks(graph: &ListenGraph) -> HashMap<String, usize> {
+    let counts: HashMap<String, usize> = graph.song_users.iter().map(|(song, users)| (song.clone(), users.len())).collect();
+    let total = counts.len();
+    top_scores(counts, &Ranker::default(), total).into_iter().enumerate().map(|(position, (song, _))| (song, position + 1)).collect()
+}
+
+//function to re-rank a recommender's output with one of the debiasing methods and keep the top k
+//songs that are not in the graph count as having no listeners (the furthest into the tail)
+pub fn debias(ranked: &[(String, f64)], graph: &ListenGraph, debiasing: &Debiasing, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+    match debiasing {
+        Debiasing::InversePopularity(beta) => {
+            let scores: HashMap<String, f64> = ranked
+                .iter()
+                .map(|(song, score)| {
+                    let listeners = graph.listener_count(song).max(1) as f64; //max(1) so unknown songs do not divide by 0
+                    (song.clone(), score / listeners.powf(*beta))
+                })
+                .collect();
+            top_scores(scores, ranker, k)
+        }
+        Debiasing::BucketQuotas(quotas) => {
+            if quotas.is_empty() {
+                return Vec::new();
+            }
+            let ranks = global_ranks(graph);
+            let total = ranks.len().max(1);
+            let mut taken = vec![0; quotas.len()];
+            let mut kept: Vec<(String, f64)> = Vec::new();
+            for (song, score) in ranked {
+                let rank = ranks.get(song).copied().unwrap_or(total); //unknown songs go in the last bucket
+                let bucket = ((rank - 1) * quotas.len() / total).min(quotas.len() - 1);
+                if taken[bucket] < quotas[bucket] && kept.len() < k {
+                    taken[bucket] += 1;
+                    kept.push((song.clone(), *score)); //ranked is already best first so the order is kept
+                }
+            }
+            kept
+        }
+        Debiasing::MaxGlobalRank(cutoff) => {
+            let ranks = global_ranks(graph);
+            ranked
+                .iter()
+                .filter(|(song, _)| ranks.get(song).is_none_or(|rank| rank > cutoff))
+                .take(k)
+                .cloned()
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::MSD;
+    use crate::test_data::record;
+
+    //Hit has 4 listeners, Mid has 2, Tail has 1
+    fn fake_data() -> Vec<MSD> {
+        let mut data = vec![
+            record("user1", "Mid", "A2", "Mid", 1),
+            record("user2", "Mid", "A2", "Mid", 1),
+            record("user1", "Tail", "A3", "Tail", 1),
+        ];
+        for user in ["user1", "user2", "user3", "user4"] {
+            data.push(record(user, "Hit", "A1", "Hit", 1));
+        }
+        data
+    }
+
+    fn candidates() -> Vec<(String, f64)> {
+        vec![("Hit".to_string(), 4.0), ("Mid".to_string(), 3.0), ("Tail".to_string(), 2.0)]
+    }
+
+    #[test]
+    fn test_global_ranks() {
+        let ranks = global_ranks(&ListenGraph::new(&fake_data()));
+        assert_eq!((ranks["Hit"], ranks["Mid"], ranks["Tail"]), (1, 2, 3));
+    }
+
+    #[test]
+    fn test_debias() {
+        let graph = ListenGraph::new(&fake_data());
+        let songs = |ranked: Vec<(String, f64)>| ranked.into_iter().map(|(song, _)| song).collect::<Vec<String>>();
+
+        //beta 0 keeps the order, beta 1 puts the tail first (2 / 1 against 3 / 2 and 4 / 4)
+        assert_eq!(songs(debias(&candidates(), &graph, &Debiasing::InversePopularity(0.0), &Ranker::default(), 3)), vec!["Hit", "Mid", "Tail"]);
+        assert_eq!(songs(debias(&candidates(), &graph, &Debiasing::InversePopularity(1.0), &Ranker::default(), 3)), vec!["Tail", "Mid", "Hit"]);
+        //no songs from the head bucket, one from each of the others
+        assert_eq!(songs(debias(&candidates(), &graph, &Debiasing::BucketQuotas(vec![0, 1, 1]), &Ranker::default(), 3)), vec!["Mid", "Tail"]);
+        //the global top 1 is cut
+        assert_eq!(songs(debias(&candidates(), &graph, &Debiasing::MaxGlobalRank(1), &Ranker::default(), 3)), vec!["Mid", "Tail"]);
+        assert_eq!(debias(&candidates(), &graph, &Debiasing::MaxGlobalRank(1), &Ranker::default(), 1).len(), 1);
+    }
+}
@@ -0,0 +1,146 @@
+use crate::exclusions::Exclusions;
+use crate::interactions::Interactions;
+use crate::scoring::{top_scores, Ranker};
+use std::collections::{HashMap, HashSet};
+
+//where a walk restarts from: a song or a user
+#[derive(Debug, Clone, PartialEq)]
+pub enum Seed {
+    Song(String), //song_id
+    User(String), //user_id
+}
+
+//settings for random walk with restart (personalized PageRank) on the user-song graph
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WalkConfig {
+    pub restart_probability: f64, //chance each step of jumping back to the seeds, higher stays closer to them
+    pub max_iterations: usize,
+    pub tolerance: f64,           //stop once the probabilities change by less than this in total (L1)
+}
+
+impl Default for WalkConfig {
+    fn default() -> WalkConfig {
+        WalkConfig { restart_probability: 0.15, max_iterations: 100, tolerance: 1e-8 }
+    }
+}
+
+//function to rank songs by how likely a walker that keeps restarting at the seeds is to be standing on them
+//each step goes song -> random listener -> random song that listener played (find_more_songs does two of these by hand)
+//seed songs, songs a seed user already heard and excluded songs are left out. Returns up to k (song_id, probability) pairs.
+pub fn random_walk_with_restart(interactions: &Interactions, seeds: &[Seed], config: &WalkConfig, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+    let num_songs = interactions.num_songs();
+    let num_users = interactions.num_users();
+
+    //restart distribution, split evenly between the seeds that are in the data
+    let mut restart_songs = vec![0.0; num_songs];
+    let mut restart_users = vec![0.0; num_users];
+    let mut excluded: HashSet<usize> = HashSet::new();
+    let known: Vec<&Seed> = seeds
+        .iter()
+        .filter(|seed| match seed {
+            Seed::Song(song_id) => interactions.song_index.contains_key(song_id),
+            Seed::User(user_id) => interactions.user_index.contains_key(user_id),
+        })
+        .collect();
+    if known.is_empty() {
+        return Vec::new();
+    }
+    let share = 1.0 / known.len() as f64;
+    for seed in known {
+        match seed {
+            Seed::Song(song_id) => {
+                let song = interactions.song_index[song_id];
+                restart_songs[song] += share;
+                excluded.insert(song);
+            }
+            Seed::User(user_id) => {
+                let user = interactions.user_index[user_id];
+                restart_users[user] += share;
+                excluded.extend(interactions.by_user[user].iter().map(|&(song, _)| song));
+            }
+        }
+    }
+
+    let mut songs = restart_songs.clone();
+    let mut users = restart_users.clone();
+    let keep = 1.0 - config.restart_probability;
+
+    for _ in 0..config.max_iterations {
+        let mut next_songs: Vec<f64> = restart_songs.iter().map(|p| p * config.restart_probability).collect();
+        let mut next_users: Vec<f64> = restart_users.iter().map(|p| p * config.restart_probability).collect();
+
+        //songs hand their probability to their listeners, users to their songs, split evenly
+        for (song, listeners) in interactions.by_song.iter().enumerate() {
+            if !listeners.is_empty() {
+                let step = keep * songs[song] / listeners.len() as f64;
+                for &(user, _) in listeners {
+                    next_users[user] += step;
+                }
+            }
+        }
+        for (user, played) in interactions.by_user.iter().enumerate() {
+            if !played.is_empty() {
+                let step = keep * users[user] / played.len() as f64;
+                for &(song, _) in played {
+                    next_songs[song] += step;
+                }
+            }
+        }
+
+        let change: f64 = songs.iter().zip(&next_songs).chain(users.iter().zip(&next_users)).map(|(a, b)| (a - b).abs()).sum();
+        songs = next_songs;
+        users = next_users;
+        if change < config.tolerance {
+            break;
+        }
+    }
+
+    let mut song_score: HashMap<String, f64> = HashMap::new();
+    for (song, probability) in songs.into_iter().enumerate() {
+        if probability > 0.0 && !excluded.contains(&song) && !exclusions.excludes_song(&interactions.song_ids[song]) {
+            song_score.insert(interactions.song_ids[song].clone(), probability);
+        }
+    }
+    top_scores(song_score, ranker, k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::record;
+
+    //chain S1 - user1 - S2 - user2 - S3, plus S4 in a separate component
+    fn fake_interactions() -> Interactions {
+        Interactions::new(&[
+            record("user1", "S1", "A1", "Song 1", 1),
+            record("user1", "S2", "A1", "Song 2", 1),
+            record("user2", "S2", "A1", "Song 2", 1),
+            record("user2", "S3", "A1", "Song 3", 1),
+            record("user3", "S4", "A2", "Song 4", 1),
+        ])
+    }
+
+    #[test]
+    fn test_walk_from_song() {
+        let interactions = fake_interactions();
+        let ranked = random_walk_with_restart(&interactions, &[Seed::Song("S1".to_string())], &WalkConfig::default(), &Exclusions::new(), &Ranker::default(), 10);
+        //closer songs get more probability, the seed and the unreachable song are not returned
+        let songs: Vec<&str> = ranked.iter().map(|(song, _)| song.as_str()).collect();
+        assert_eq!(songs, vec!["S2", "S3"]);
+        //an excluded song is still walked through, it is just not returned
+        let ranked = random_walk_with_restart(&interactions, &[Seed::Song("S1".to_string())], &WalkConfig::default(), &Exclusions::new().with_songs(["S2"]), &Ranker::default(), 10);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].0, "S3");
+    }
+
+    #[test]
+    fn test_walk_from_user() {
+        let interactions = fake_interactions();
+        let seeds = [Seed::User("user1".to_string()), Seed::Song("nothing".to_string())];
+        let ranked = random_walk_with_restart(&interactions, &seeds, &WalkConfig::default(), &Exclusions::new(), &Ranker::default(), 10);
+        //user1 already heard S1 and S2, unknown seeds are skipped
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].0, "S3");
+        assert!(random_walk_with_restart(&interactions, &[], &WalkConfig::default(), &Exclusions::new(), &Ranker::default(), 10).is_empty());
+    }
+}
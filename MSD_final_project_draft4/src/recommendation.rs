@@ -0,0 +1,230 @@
+use crate::csv_reader::MSD;
+use crate::explain::Explanation;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+//what a recommendation's score means, so callers do not have to guess if a number is listeners, a probability, ...
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreType {
+    ListenerCount, //number of listeners (possibly decayed across hops)
+    WeightedCount, //listeners weighted by listen_count or by how much of the seed they play
+    Similarity,    //an item-item or user-user similarity, or a sum of them
+    Probability,   //stationary probability of a random walk
+    ModelScore,    //output of a trained model (ALS, BPR, EASE, PureSVD, embeddings)
+}
+
+//which recommender produced a recommendation
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    MostPopular,
+    FindMoreSongs,
+    Weighted,
+    ItemSimilarity,
+    Expansion,
+    UserKnn,
+    ItemItem,
+    Als,
+    Bpr,
+    Ease,
+    PureSvd,
+    RandomWalk,
+    Node2Vec,
+    Hnsw,
+    Playlist,
+    ArtistSeed,
+    ColdStartSameArtist, //the cold start stages, see cold_start::FallbackStage
+    ColdStartArtistNeighbours,
+    ColdStartArtistChart,
+    ColdStartGlobalChart,
+    AssociationRule,
+}
+
+//which re-ranker reordered a recommender's list, the method is still the recommender the songs came from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reranker {
+    InversePopularity, //popularity::Debiasing::InversePopularity
+    BucketQuotas,      //popularity::Debiasing::BucketQuotas
+    MaxGlobalRank,     //popularity::Debiasing::MaxGlobalRank
+    Diversity,         //diversity::diversify (MMR with a per-artist cap)
+}
+
+//one recommended song with everything a downstream consumer needs to show or log it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Recommendation {
+    pub song_id: String,
+    pub title: String,
+    pub artist: String,
+    pub score: f64,
+    pub score_type: ScoreType,
+    pub listeners: usize, //supporting listeners: shared listeners with the seed when there is one, otherwise all listeners of the song
+    pub rank: usize,      //1 for the best recommendation
+    pub method: Method,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reranked_by: Option<Reranker>, //None when the list is in the recommender's own order
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<Explanation>, //filled in by explain::explain_all, or by the recommender itself (find_more_songs)
+}
+
+impl fmt::Display for Recommendation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "#{} '{}' by {} (score {:.3} {:?}, {} listeners, {:?}",
+            self.rank, self.title, self.artist, self.score, self.score_type, self.listeners, self.method
+        )?;
+        if let Some(reranker) = self.reranked_by {
+            write!(f, " reranked by {:?}", reranker)?;
+        }
+        write!(f, ")")?;
+        if let Some(explanation) = &self.explanation {
+            write!(f, " {}", explanation.sentence())?;
+        }
+        Ok(())
+    }
+}
+
+//song_id -> title, artist and listener count, used to fill in recommendations
+pub struct SongCatalog {
+    titles: HashMap<String, String>,
+    artists: HashMap<String, String>,
+    listeners: HashMap<String, usize>,
+    by_title: HashMap<String, Vec<String>>, //title -> song_ids in the order they are first seen, for the title based recommenders
+}
+
+impl SongCatalog {
+    pub fn new(data: &[MSD]) -> SongCatalog {
+        let mut titles = HashMap::new();
+        let mut artists = HashMap::new();
+        let mut by_title = HashMap::new();
+        let mut users: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for record in data {
+            titles.entry(record.song_id.clone()).or_insert_with(|| record.title.clone());
+            artists.entry(record.song_id.clone()).or_insert_with(|| record.artist_name.clone());
+            let song_ids: &mut Vec<String> = by_title.entry(record.title.clone()).or_default();
+            if !song_ids.contains(&record.song_id) {
+                song_ids.push(record.song_id.clone());
+            }
+            users.entry(record.song_id.as_str()).or_default().insert(record.user_id.as_str());
+        }
+        let listeners = users.into_iter().map(|(song, set)| (song.to_string(), set.len())).collect();
+        SongCatalog { titles, artists, listeners, by_title }
+    }
+
+    //title for a song_id (empty if the song is not in the data)
+    pub fn title(&self, song_id: &str) -> &str {
+        self.titles.get(song_id).map_or("", |title| title.as_str())
+    }
+
+    //number of listeners of a song_id (0 if the song is not in the data)
+    pub fn listener_count(&self, song_id: &str) -> usize {
+        self.listeners.get(song_id).copied().unwrap_or(0)
+    }
+
+    //every song_id with this title (two artists can both have an "Intro")
+    pub fn song_ids(&self, title: &str) -> &[String] {
+        self.by_title.get(title).map_or(&[], |song_ids| song_ids.as_slice())
+    }
+
+    //song_id for a title (first one in the data)
+    pub fn song_id(&self, title: &str) -> Option<&String> {
+        self.by_title.get(title).and_then(|song_ids| song_ids.first())
+    }
+
+    //function to turn a ranked (song_id, score) list into recommendations
+    //support is the shared-listener count per song when the recommender has a seed, None uses each song's total listeners
+    pub fn recommendations(&self, ranked: &[(String, f64)], method: Method, score_type: ScoreType, support: Option<&HashMap<String, usize>>) -> Vec<Recommendation> {
+        ranked
+            .iter()
+            .enumerate()
+            .map(|(position, (song_id, score))| {
+                let listeners = match support {
+                    Some(shared) => shared.get(song_id).copied().unwrap_or(0),
+                    None => self.listeners.get(song_id).copied().unwrap_or(0),
+                };
+                Recommendation {
+                    song_id: song_id.clone(),
+                    title: self.titles.get(song_id).cloned().unwrap_or_default(),
+                    artist: self.artists.get(song_id).cloned().unwrap_or_default(),
+                    score: *score,
+                    score_type,
+                    listeners,
+                    rank: position + 1,
+                    method,
+                    reranked_by: None,
+                    explanation: None,
+                }
+            })
+            .collect()
+    }
+}
+
+//function to mark a list of recommendations as reordered by a re-ranker
+pub fn reranked(recommendations: Vec<Recommendation>, reranker: Reranker) -> Vec<Recommendation> {
+    recommendations
+        .into_iter()
+        .map(|mut recommendation| {
+            recommendation.reranked_by = Some(reranker);
+            recommendation
+        })
+        .collect()
+}
+
+//function to write recommendations as a JSON array
+pub fn to_json(recommendations: &[Recommendation]) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(recommendations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::record;
+
+    #[test]
+    fn test_recommendations() {
+        let data = vec![
+            record("user1", "S1", "A1", "Song 1", 1),
+            record("user2", "S1", "A1", "Song 1", 1),
+            record("user1", "S2", "A2", "Song 2", 1),
+        ];
+        let catalog = SongCatalog::new(&data);
+        let ranked = vec![("S1".to_string(), 0.9), ("S2".to_string(), 0.5)];
+
+        let plain = catalog.recommendations(&ranked, Method::Als, ScoreType::ModelScore, None);
+        assert_eq!(plain[0].title, "Song 1");
+        assert_eq!(plain[0].artist, "A1");
+        assert_eq!(plain[0].listeners, 2);
+        assert_eq!(plain[1].rank, 2);
+
+        let shared: HashMap<String, usize> = [("S2".to_string(), 1)].into_iter().collect();
+        let supported = catalog.recommendations(&ranked, Method::ItemSimilarity, ScoreType::Similarity, Some(&shared));
+        assert_eq!(supported[0].listeners, 0);
+        assert_eq!(supported[1].listeners, 1);
+        assert_eq!(catalog.song_id("Song 2"), Some(&"S2".to_string()));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let data = vec![record("user1", "S1", "A1", "Song 1", 1)];
+        let catalog = SongCatalog::new(&data);
+        let recommendations = catalog.recommendations(&[("S1".to_string(), 2.0)], Method::MostPopular, ScoreType::ListenerCount, None);
+        let json = to_json(&recommendations).unwrap();
+
+        let parsed: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed[0]["song_id"], "S1");
+        assert_eq!(parsed[0]["score_type"], "listener_count");
+        assert_eq!(parsed[0]["method"], "most_popular");
+        assert_eq!(parsed[0]["rank"], 1);
+        assert!(parsed[0].get("reranked_by").is_none());
+
+        //a re-ranked list keeps its method and says what reordered it
+        let debiased = reranked(recommendations, Reranker::MaxGlobalRank);
+        let parsed: serde_json::Value = serde_json::from_str(&to_json(&debiased).unwrap()).unwrap();
+        assert_eq!(parsed[0]["method"], "most_popular");
+        assert_eq!(parsed[0]["reranked_by"], "max_global_rank");
+        assert!(debiased[0].to_string().contains("MostPopular reranked by MaxGlobalRank)"));
+    }
+}
@@ -0,0 +1,119 @@
+use crate::recommendation::SongCatalog;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+//function that counts, for every item, how many users in the map have it (the co-listen count)
+//works for any kind of item: song titles for most_popular_song, artist_ids for the artist recommender, so the caller
+//says how an item is checked against its Exclusions (excludes_title or excludes_artist)
+pub fn co_listen_counts(user_items_hm: &HashMap<String, HashSet<String>>, excluded: impl Fn(&str) -> bool) -> HashMap<String, usize> {
+    let mut item_score: HashMap<String, usize> = HashMap::new(); //item as key and number of users as value
+
+    for items in user_items_hm.values() { //iterates through each user's set of items
+        for item in items {
+            if !excluded(item) { //leaves seeds, blocklisted items and history out of the counts
+                *item_score.entry(item.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+    item_score
+}
+
+//function to sort scores from highest to lowest and keep the top k
+//ties are broken by the ranker's policy, so the order is the same every run
+pub fn top_scores<S: Copy + PartialOrd>(scores: HashMap<String, S>, ranker: &Ranker, k: usize) -> Vec<(String, S)> {
+    ranker.rerank(scores.into_iter().collect(), k)
+}
+
+//how two songs with exactly the same score are ordered
+//every policy falls back to song_id, so the order is total and the same on every run
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TieBreak {
+    #[default]
+    SongId,     //smaller song_id first (or smaller key, for artists and other items)
+    Title,      //alphabetical by title
+    Popularity, //more listeners in the whole data set first
+}
+
+//ranks scores with a configurable tie-break
+//titles and listener counts come from the SongCatalog the recommendations are filled in from,
+//the default ranker breaks ties by song_id and needs no catalog
+#[derive(Clone, Copy, Default)]
+pub struct Ranker<'a> {
+    pub tie_break: TieBreak,
+    catalog: Option<&'a SongCatalog>,
+}
+
+impl<'a> Ranker<'a> {
+    pub fn new(tie_break: TieBreak, catalog: &'a SongCatalog) -> Ranker<'a> {
+        Ranker { tie_break, catalog: Some(catalog) }
+    }
+
+    //order of two song_ids whose scores are equal
+    pub fn tie_order(&self, a: &str, b: &str) -> Ordering {
+        let by_policy = match (self.tie_break, self.catalog) {
+            (TieBreak::Title, Some(catalog)) => catalog.title(a).cmp(catalog.title(b)),
+            (TieBreak::Popularity, Some(catalog)) => catalog.listener_count(b).cmp(&catalog.listener_count(a)),
+            _ => Ordering::Equal,
+        };
+        by_policy.then_with(|| a.cmp(b))
+    }
+
+    //order of two titles whose counts are equal, for the title based functions (most_popular_song)
+    //each title is ranked as the song the catalog gives for it, titles it does not know fall back to alphabetical
+    pub fn title_tie_order(&self, a: &str, b: &str) -> Ordering {
+        let song_id = |title| self.catalog.and_then(|catalog| catalog.song_id(title));
+        match (song_id(a), song_id(b)) {
+            (Some(a_id), Some(b_id)) => self.tie_order(a_id, b_id),
+            _ => a.cmp(b),
+        }
+    }
+
+    //function to sort a ranked list by score with the ranker's tie-break and keep the top k
+    pub fn rerank<S: Copy + PartialOrd>(&self, mut ranked: Vec<(String, S)>, k: usize) -> Vec<(String, S)> {
+        ranked.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(Ordering::Equal) //NaN scores are treated as equal
+                .then_with(|| self.tie_order(&a.0, &b.0))
+        });
+        ranked.truncate(k);
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_data::record;
+
+    #[test]
+    fn test_top_scores() {
+        let scores: HashMap<String, usize> = [("b".to_string(), 2), ("a".to_string(), 2), ("c".to_string(), 5)].into_iter().collect();
+        let ranked = top_scores(scores, &Ranker::default(), 2);
+        //c has the highest score, a beats b on the tie
+        assert_eq!(ranked, vec![("c".to_string(), 5), ("a".to_string(), 2)]);
+    }
+
+    #[test]
+    fn test_ranker_tie_break() {
+        //S1 "Zebra" has 1 listener, S2 "Apple" has 1, S3 "Mango" has 2
+        let data = vec![
+            record("user1", "S1", "A1", "Zebra", 1),
+            record("user1", "S2", "A1", "Apple", 1),
+            record("user1", "S3", "A1", "Mango", 1),
+            record("user2", "S3", "A1", "Mango", 1),
+        ];
+        let catalog = SongCatalog::new(&data);
+        let scores: HashMap<String, f64> = ["S1", "S2", "S3"].iter().map(|song| (song.to_string(), 1.0)).collect();
+        let order = |tie_break| top_scores(scores.clone(), &Ranker::new(tie_break, &catalog), 3).into_iter().map(|(song, _)| song).collect::<Vec<String>>();
+        assert_eq!(order(TieBreak::SongId), vec!["S1", "S2", "S3"]);
+        assert_eq!(order(TieBreak::Title), vec!["S2", "S3", "S1"]);
+        assert_eq!(order(TieBreak::Popularity), vec!["S3", "S1", "S2"]); //S1 and S2 tie on listeners too, song_id decides
+        //a higher score still beats any tie-break
+        let ranker = Ranker::new(TieBreak::Popularity, &catalog);
+        assert_eq!(ranker.rerank(vec![("S3".to_string(), 1.0), ("S1".to_string(), 2.0)], 1), vec![("S1".to_string(), 2.0)]);
+        //titles are ranked as their songs
+        assert_eq!(ranker.title_tie_order("Zebra", "Mango"), Ordering::Greater);
+        assert_eq!(Ranker::new(TieBreak::SongId, &catalog).title_tie_order("Zebra", "Apple"), Ordering::Less);
+        assert_eq!(ranker.title_tie_order("Unknown", "Apple"), Ordering::Greater);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::exclusions::Exclusions;
+use crate::listen_graph::ListenGraph;
+use crate::scoring::{top_scores, Ranker};
+use std::collections::HashMap;
+
+//how two songs' listener sets are compared
+//most_popular_song ranks by the raw co-listen count, which mostly rewards songs everybody plays;
+//these measures divide the popularity back out so results reflect affinity to the seed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Similarity {
+    Jaccard,          //shared / (listeners of seed + listeners of song - shared)
+    Cosine,           //shared / sqrt(listeners of seed * listeners of song)
+    Conditional(f64), //shared / (listeners of seed * listeners of song^alpha), alpha = 0 is plain P(song | seed)
+    Lift,             //P(seed and song) / (P(seed) * P(song))
+    Pmi,              //log of the lift
+}
+
+impl Similarity {
+    //shared = number of users who played both, seed_count and song_count = listeners of each, total_users = all users
+    pub fn score(&self, shared: usize, seed_count: usize, song_count: usize, total_users: usize) -> f64 {
+        if shared == 0 || seed_count == 0 || song_count == 0 {
+            return 0.0;
+        }
+        let (shared, seed_count, song_count, total_users) = (shared as f64, seed_count as f64, song_count as f64, total_users as f64);
+        match self {
+            Similarity::Jaccard => shared / (seed_count + song_count - shared),
+            Similarity::Cosine => shared / (seed_count * song_count).sqrt(),
+            //listener counts, not P(song) = song_count / total_users: the two differ by the constant total_users^alpha so the ranking is the same
+            Similarity::Conditional(alpha) => shared / (seed_count * song_count.powf(*alpha)),
+            Similarity::Lift => shared * total_users / (seed_count * song_count),
+            Similarity::Pmi => (shared * total_users / (seed_count * song_count)).ln(),
+        }
+    }
+}
+
+//function to score every song that shares a listener with the seed
+//returns song_id -> similarity (the seed itself is left out)
+pub fn song_similarities(seed_song_id: &str, graph: &ListenGraph, similarity: Similarity) -> HashMap<String, f64> {
+    let seed_count = graph.listener_count(seed_song_id);
+    let total_users = graph.total_users();
+
+    graph
+        .co_listeners(seed_song_id)
+        .into_iter()
+        .map(|(song, shared)| {
+            let score = similarity.score(shared, seed_count, graph.listener_count(&song), total_users);
+            (song, score)
+        })
+        .collect()
+}
+
+//function to find the k songs most similar to the seed that are not excluded, best first
+pub fn similar_songs(seed_song_id: &str, graph: &ListenGraph, similarity: Similarity, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+    top_scores(exclusions.filter(song_similarities(seed_song_id, graph, similarity)), ranker, k)
+}
+
+//function to answer "what should this user listen to next" from their whole history
+//every song the user played adds its similarity to each candidate song, and songs already heard or excluded are left out
+//returns up to k (song_id, summed similarity) pairs, best first (empty for an unknown user)
+pub fn recommend_for_user(user_id: &str, graph: &ListenGraph, similarity: Similarity, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+    let history = match graph.user_songs.get(user_id) {
+        Some(history) => history,
+        None => return Vec::new(),
+    };
+
+    let mut heard_in_order: Vec<&String> = history.iter().collect();
+    heard_in_order.sort(); //similarities are added up in the same order every run so the sums match exactly
+    let mut song_score: HashMap<String, f64> = HashMap::new();
+    for heard in heard_in_order {
+        for (song, score) in song_similarities(heard, graph, similarity) {
+            if !history.contains(&song) && !exclusions.excludes_song(&song) {
+                *song_score.entry(song).or_insert(0.0) += score;
+            }
+        }
+    }
+    top_scores(song_score, ranker, k)
+}
+
+//the item-item recommender with its graph and measure chosen up front, so a query is just recommend_for_user(user_id, k)
+//(the free function above takes them on every call, for comparing measures on one graph)
+pub struct ItemItem<'a> {
+    pub graph: &'a ListenGraph,
+    pub similarity: Similarity,
+    pub exclusions: Exclusions, //never recommended, on top of the user's own history
+    pub ranker: Ranker<'a>,     //how songs with the same score are ordered
+}
+
+impl<'a> ItemItem<'a> {
+    //cosine is the default measure: it does not need a tuning value and does not favour songs with few listeners. Nothing is excluded, ties go by song_id
+    pub fn new(graph: &'a ListenGraph) -> ItemItem<'a> {
+        ItemItem { graph, similarity: Similarity::Cosine, exclusions: Exclusions::new(), ranker: Ranker::default() }
+    }
+
+    pub fn recommend_for_user(&self, user_id: &str, k: usize) -> Vec<(String, f64)> {
+        recommend_for_user(user_id, self.graph, self.similarity, &self.exclusions, &self.ranker, k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::MSD;
+    use crate::recommendation::SongCatalog;
+    use crate::scoring::TieBreak;
+    use crate::test_data::record;
+
+    //S1 is the seed, S2 is a niche song only S1 listeners play, S3 is a hit that everyone plays
+    fn fake_data() -> Vec<MSD> {
+        let mut data = vec![
+            record("user1", "S1", "A1", "Seed", 1),
+            record("user1", "S2", "A2", "Niche", 1),
+            record("user2", "S1", "A1", "Seed", 1),
+            record("user2", "S2", "A2", "Niche", 1),
+            record("user1", "S3", "A3", "Hit", 1),
+            record("user2", "S3", "A3", "Hit", 1),
+            record("user3", "S1", "A1", "Seed", 1),
+            record("user3", "S3", "A3", "Hit", 1),
+        ];
+        for user in ["user4", "user5", "user6", "user7"] {
+            data.push(record(user, "S3", "A3", "Hit", 1));
+        }
+        data
+    }
+
+    #[test]
+    fn test_similarity_score() {
+        //2 shared listeners, 3 seed listeners, 4 song listeners, 10 users
+        assert!((Similarity::Jaccard.score(2, 3, 4, 10) - 2.0 / 5.0).abs() < 1e-12);
+        assert!((Similarity::Cosine.score(2, 3, 4, 10) - 2.0 / 12f64.sqrt()).abs() < 1e-12);
+        assert!((Similarity::Conditional(0.0).score(2, 3, 4, 10) - 2.0 / 3.0).abs() < 1e-12);
+        assert!((Similarity::Lift.score(2, 3, 4, 10) - 20.0 / 12.0).abs() < 1e-12);
+        assert!((Similarity::Pmi.score(2, 3, 4, 10) - (20.0f64 / 12.0).ln()).abs() < 1e-12);
+        assert_eq!(Similarity::Lift.score(0, 3, 4, 10), 0.0);
+    }
+
+    #[test]
+    fn test_conditional_alpha() {
+        //alpha 0.5: 2 / (3 * sqrt(4)) = 1 / 3, the number of users does not change it
+        assert!((Similarity::Conditional(0.5).score(2, 3, 4, 10) - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(Similarity::Conditional(0.5).score(2, 3, 4, 10), Similarity::Conditional(0.5).score(2, 3, 4, 1000));
+        //alpha 1: 2 / (3 * 4)
+        assert!((Similarity::Conditional(1.0).score(2, 3, 4, 10) - 2.0 / 12.0).abs() < 1e-12);
+        //a bigger alpha penalizes the song with more listeners harder
+        let popular = |alpha| Similarity::Conditional(alpha).score(2, 3, 16, 100) / Similarity::Conditional(alpha).score(2, 3, 4, 100);
+        assert!(popular(0.8) < popular(0.2));
+    }
+
+    #[test]
+    fn test_similar_songs() {
+        let graph = ListenGraph::new(&fake_data());
+        //the raw co-listen count prefers the hit (3 shared listeners against 2)
+        assert_eq!(graph.co_listeners("S1")["S3"], 3);
+        //every normalized measure prefers the niche song
+        for similarity in [Similarity::Jaccard, Similarity::Cosine, Similarity::Conditional(0.5), Similarity::Lift, Similarity::Pmi] {
+            let similar = similar_songs("S1", &graph, similarity, &Exclusions::new(), &Ranker::default(), 1);
+            assert_eq!(similar[0].0, "S2", "{:?}", similarity);
+            //with the niche song excluded the next best song comes back instead
+            let without_s2 = similar_songs("S1", &graph, similarity, &Exclusions::new().with_songs(["S2"]), &Ranker::default(), 10);
+            assert!(!without_s2.is_empty() && without_s2.iter().all(|(song, _)| song != "S2"), "{:?}", similarity);
+        }
+    }
+
+    #[test]
+    fn test_recommend_for_user() {
+        let mut data = fake_data();
+        data.push(record("user8", "S1", "A1", "Seed", 1));
+        data.push(record("user8", "S4", "A4", "Fresh", 1));
+        let graph = ListenGraph::new(&data);
+
+        //user3 has heard S1 and S3, so only S2 and S4 can come back
+        let recommended = recommend_for_user("user3", &graph, Similarity::Cosine, &Exclusions::new(), &Ranker::default(), 10);
+        let songs: Vec<&str> = recommended.iter().map(|(song, _)| song.as_str()).collect();
+        assert_eq!(songs.len(), 2);
+        assert!(songs.contains(&"S2") && songs.contains(&"S4"));
+        //S2 is similar to both songs user3 played, S4 only to S1
+        assert_eq!(songs[0], "S2");
+        assert!(recommend_for_user("nobody", &graph, Similarity::Cosine, &Exclusions::new(), &Ranker::default(), 10).is_empty());
+        //excluding S2's artist leaves only S4
+        let no_a2 = Exclusions::new().with_artists(["A2"], &data);
+        assert_eq!(recommend_for_user("user3", &graph, Similarity::Cosine, &no_a2, &Ranker::default(), 10)[0].0, "S4");
+        assert_eq!(ItemItem { exclusions: no_a2, ..ItemItem::new(&graph) }.recommend_for_user("user3", 10).len(), 1);
+
+        //the two argument entry point uses cosine unless told otherwise
+        let item_item = ItemItem::new(&graph);
+        assert_eq!(item_item.recommend_for_user("user3", 10), recommended);
+        let lift = ItemItem { similarity: Similarity::Lift, ..ItemItem::new(&graph) };
+        assert_eq!(lift.recommend_for_user("user3", 10), recommend_for_user("user3", &graph, Similarity::Lift, &Exclusions::new(), &Ranker::default(), 10));
+    }
+
+    #[test]
+    fn test_similar_songs_tie_break() {
+        //S2 "Zebra" and S3 "Apple" are each played by the seed's only listener, so they tie on every measure
+        let data = vec![
+            record("user1", "S1", "A1", "Seed", 1),
+            record("user1", "S2", "A2", "Zebra", 1),
+            record("user1", "S3", "A3", "Apple", 1),
+        ];
+        let graph = ListenGraph::new(&data);
+        let catalog = SongCatalog::new(&data);
+        let first = |ranker: &Ranker| similar_songs("S1", &graph, Similarity::Cosine, &Exclusions::new(), ranker, 1)[0].0.clone();
+        assert_eq!(first(&Ranker::default()), "S2");
+        assert_eq!(first(&Ranker::new(TieBreak::Title, &catalog)), "S3");
+    }
+}
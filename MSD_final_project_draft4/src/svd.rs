@@ -0,0 +1,150 @@
+use crate::factors::FactorModel;
+use crate::interactions::Interactions;
+use crate::linalg::{orthonormalize_columns, symmetric_eigen};
+use crate::rng::Rng;
+use crate::weighting::Weighting;
+
+//settings for PureSVD (Cremonesi et al.) computed with randomized SVD (Halko, Martinsson and Tropp)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SvdConfig {
+    pub rank: usize,             //number of singular vectors kept
+    pub oversampling: usize,     //extra random directions sampled on top of rank, makes the top vectors more accurate
+    pub power_iterations: usize, //extra passes over the matrix, help when singular values fall off slowly
+    pub weighting: Weighting,    //how listen_count fills the user x song matrix
+    pub seed: u64,
+}
+
+impl Default for SvdConfig {
+    fn default() -> SvdConfig {
+        SvdConfig { rank: 50, oversampling: 10, power_iterations: 2, weighting: Weighting::Binary, seed: 42 }
+    }
+}
+
+//A * M, where A is users x songs and M is songs x l (rows of M are songs)
+fn times(interactions: &Interactions, config: &SvdConfig, m: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let width = m.first().map_or(0, |row| row.len());
+    interactions
+        .by_user
+        .iter()
+        .map(|songs| {
+            let mut row = vec![0.0; width];
+            for &(song, plays) in songs {
+                let weight = config.weighting.weight(plays as u64);
+                for (value, m_value) in row.iter_mut().zip(&m[song]) {
+                    *value += weight * m_value;
+                }
+            }
+            row
+        })
+        .collect()
+}
+
+//A^T * M, where M is users x l (rows of M are users)
+fn transpose_times(interactions: &Interactions, config: &SvdConfig, m: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let width = m.first().map_or(0, |row| row.len());
+    interactions
+        .by_song
+        .iter()
+        .map(|users| {
+            let mut row = vec![0.0; width];
+            for &(user, plays) in users {
+                let weight = config.weighting.weight(plays as u64);
+                for (value, m_value) in row.iter_mut().zip(&m[user]) {
+                    *value += weight * m_value;
+                }
+            }
+            row
+        })
+        .collect()
+}
+
+//function to compute a truncated SVD A ~ U S V^T of the user x song matrix without ever making it dense
+//returns a FactorModel with user vectors U and song vectors V S (so dot products are the reconstructed scores
+//and cosine between song vectors gives "similar songs"), plus the singular values largest first
+pub fn train_svd(interactions: &Interactions, config: &SvdConfig) -> (FactorModel, Vec<f64>) {
+    let mut rng = Rng::new(config.seed);
+    let width = (config.rank + config.oversampling).min(interactions.num_users()).min(interactions.num_songs());
+
+    //Y = A * Omega with a random Gaussian Omega, then a few power iterations, then Q = orthonormal basis of Y
+    let omega: Vec<Vec<f64>> = (0..interactions.num_songs()).map(|_| (0..width).map(|_| rng.normal()).collect()).collect();
+    let mut q = times(interactions, config, &omega);
+    orthonormalize_columns(&mut q);
+    for _ in 0..config.power_iterations {
+        let mut z = transpose_times(interactions, config, &q);
+        orthonormalize_columns(&mut z);
+        q = times(interactions, config, &z);
+        orthonormalize_columns(&mut q);
+    }
+
+    //B^T = A^T Q is songs x width, and B B^T = Q^T A A^T Q is a small width x width matrix
+    let bt = transpose_times(interactions, config, &q);
+    let mut bbt = vec![vec![0.0; width]; width];
+    for row in &bt {
+        for i in 0..width {
+            for j in 0..width {
+                bbt[i][j] += row[i] * row[j];
+            }
+        }
+    }
+
+    //B B^T = W S^2 W^T, so U = Q W and V S = B^T W
+    let (eigenvalues, eigenvectors) = symmetric_eigen(&bbt);
+    let rank = config.rank.min(width);
+    let singular_values: Vec<f64> = eigenvalues.iter().take(rank).map(|value| value.max(0.0).sqrt()).collect();
+
+    let project = |rows: &[Vec<f64>]| -> Vec<Vec<f64>> {
+        rows.iter().map(|row| eigenvectors.iter().take(rank).map(|w| row.iter().zip(w).map(|(x, y)| x * y).sum()).collect()).collect()
+    };
+    let user_factors = project(&q);
+    let item_factors = project(&bt);
+
+    (FactorModel { user_factors, item_factors }, singular_values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csv_reader::MSD;
+    use crate::exclusions::Exclusions;
+    use crate::scoring::Ranker;
+    use crate::test_data::record;
+
+    fn fake_data() -> Vec<MSD> {
+        let mut data = Vec::new();
+        for user in ["user1", "user2", "user3"] {
+            for song in ["A1", "A2", "A3"] {
+                data.push(record(user, song, "artistA", song, 1));
+            }
+        }
+        for user in ["user4", "user5"] {
+            for song in ["B1", "B2"] {
+                data.push(record(user, song, "artistB", song, 1));
+            }
+        }
+        data.push(record("user6", "A1", "artistA", "A1", 1));
+        data.push(record("user6", "A2", "artistA", "A2", 1));
+        data
+    }
+
+    #[test]
+    fn test_svd_singular_values() {
+        let interactions = Interactions::new(&fake_data());
+        let config = SvdConfig { rank: 2, oversampling: 3, ..SvdConfig::default() };
+        let (_, singular_values) = train_svd(&interactions, &config);
+        //the B block (2 users x 2 songs of ones) has singular value 2
+        assert_eq!(singular_values.len(), 2);
+        assert!(singular_values[0] > singular_values[1]);
+        assert!((singular_values[1] - 2.0).abs() < 1e-6, "{:?}", singular_values);
+    }
+
+    #[test]
+    fn test_svd_recommend() {
+        let interactions = Interactions::new(&fake_data());
+        let config = SvdConfig { rank: 2, oversampling: 3, ..SvdConfig::default() };
+        let (model, _) = train_svd(&interactions, &config);
+
+        //user6 is missing A3 from the A block
+        assert_eq!(model.recommend(&interactions, "user6", &Exclusions::new(), &Ranker::default(), 1)[0].0, "A3");
+        assert_eq!(model.similar_songs(&interactions, "B1", &Exclusions::new(), &Ranker::default(), 1)[0].0, "B2");
+    }
+}
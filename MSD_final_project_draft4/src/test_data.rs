@@ -0,0 +1,15 @@
+use crate::csv_reader::MSD;
+
+//builds one fake MSD row for tests (columns that are not passed in are filled with "0")
+pub fn record(user_id: &str, song_id: &str, artist_id: &str, title: &str, listen_count: u32) -> MSD {
+    MSD {
+        unknown: "0".to_string(),
+        user_id: user_id.to_string(),
+        song_id: song_id.to_string(),
+        listen_count: listen_count.to_string(),
+        track_id: format!("TR{}", song_id), //one track per song unless a test overrides it
+        artist_id: artist_id.to_string(),
+        artist_name: artist_id.to_string(), //artist name is the same as the id in fake data
+        title: title.to_string(),
+    }
+}
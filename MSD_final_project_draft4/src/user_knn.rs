@@ -0,0 +1,149 @@
+use crate::csv_reader::MSD;
+use crate::exclusions::Exclusions;
+use crate::scoring::{top_scores, Ranker};
+use crate::weighting::{users_to_weighted_songs, Weighting};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+//how two users' weighted listening histories are compared
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UserSimilarity {
+    Cosine,  //dot product of the two weight vectors over the product of their lengths
+    Jaccard, //weighted Jaccard: sum of min weights over sum of max weights (plain Jaccard with Weighting::Binary)
+}
+
+//user-based k-nearest-neighbour recommender
+pub struct UserKnn {
+    histories: HashMap<String, BTreeMap<String, f64>>, //user_id -> (song_id -> weight), sorted so similarity sums are added in a fixed order
+    song_users: HashMap<String, Vec<String>>,         //song_id -> users, to find candidate neighbours quickly
+}
+
+impl UserKnn {
+    pub fn new(data: &[MSD], weighting: Weighting) -> UserKnn {
+        let all_users: HashSet<String> = data.iter().map(|record| record.user_id.clone()).collect();
+        let histories: HashMap<String, BTreeMap<String, f64>> =
+            users_to_weighted_songs(&all_users, data, weighting).into_iter().map(|(user, songs)| (user, songs.into_iter().collect())).collect();
+
+        let mut song_users: HashMap<String, Vec<String>> = HashMap::new();
+        for (user, songs) in &histories {
+            for song in songs.keys() {
+                song_users.entry(song.clone()).or_default().push(user.clone());
+            }
+        }
+        UserKnn { histories, song_users }
+    }
+
+    fn similarity(&self, a: &BTreeMap<String, f64>, b: &BTreeMap<String, f64>, measure: UserSimilarity) -> f64 {
+        match measure {
+            UserSimilarity::Cosine => {
+                let dot: f64 = a.iter().filter_map(|(song, weight)| b.get(song).map(|other| weight * other)).sum();
+                let norm_a: f64 = a.values().map(|w| w * w).sum::<f64>().sqrt();
+                let norm_b: f64 = b.values().map(|w| w * w).sum::<f64>().sqrt();
+                if norm_a == 0.0 || norm_b == 0.0 { 0.0 } else { dot / (norm_a * norm_b) }
+            }
+            UserSimilarity::Jaccard => {
+                let mut min_sum = 0.0;
+                let mut max_sum = 0.0;
+                for (song, weight) in a {
+                    let other = b.get(song).copied().unwrap_or(0.0);
+                    min_sum += weight.min(other);
+                    max_sum += weight.max(other);
+                }
+                for (song, weight) in b {
+                    if !a.contains_key(song) {
+                        max_sum += weight; //songs only b has: min is 0, max is b's weight
+                    }
+                }
+                if max_sum == 0.0 { 0.0 } else { min_sum / max_sum }
+            }
+        }
+    }
+
+    //function to find the k users most similar to the given user (only users sharing at least one song are compared)
+    pub fn nearest_users(&self, user_id: &str, measure: UserSimilarity, k: usize) -> Vec<(String, f64)> {
+        let history = match self.histories.get(user_id) {
+            Some(history) => history,
+            None => return Vec::new(), //unknown user
+        };
+
+        let mut candidates: HashSet<&String> = HashSet::new();
+        for song in history.keys() {
+            candidates.extend(self.song_users[song].iter().filter(|other| *other != user_id));
+        }
+
+        let mut scores: HashMap<String, f64> = HashMap::new();
+        for other in candidates {
+            let score = self.similarity(history, &self.histories[other], measure);
+            if score > 0.0 {
+                scores.insert(other.clone(), score);
+            }
+        }
+        top_scores(scores, &Ranker::default(), k)
+    }
+
+    //function to recommend songs from the user's k nearest neighbours
+    //each neighbour adds similarity * their weight for the song, and songs the user already heard are left out
+    pub fn recommend(&self, user_id: &str, measure: UserSimilarity, neighbours: usize, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+        let history = match self.histories.get(user_id) {
+            Some(history) => history,
+            None => return Vec::new(),
+        };
+
+        let mut song_score: HashMap<String, f64> = HashMap::new();
+        for (neighbour, similarity) in self.nearest_users(user_id, measure, neighbours) {
+            for (song, weight) in &self.histories[&neighbour] {
+                if !history.contains_key(song) && !exclusions.excludes_song(song) {
+                    *song_score.entry(song.clone()).or_insert(0.0) += similarity * weight;
+                }
+            }
+        }
+        top_scores(song_score, ranker, k)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exclusions::Exclusions;
+    use crate::test_data::record;
+
+    fn fake_data() -> Vec<MSD> {
+        vec![
+            //user1 and user2 share two songs, user3 shares one with user1
+            record("user1", "S1", "A1", "Song 1", 1),
+            record("user1", "S2", "A1", "Song 2", 1),
+            record("user2", "S1", "A1", "Song 1", 1),
+            record("user2", "S2", "A1", "Song 2", 1),
+            record("user2", "S3", "A2", "Song 3", 1),
+            record("user3", "S2", "A1", "Song 2", 1),
+            record("user3", "S4", "A3", "Song 4", 1),
+            record("user3", "S5", "A3", "Song 5", 1),
+            record("user4", "S6", "A4", "Song 6", 1), //shares nothing with user1
+        ]
+    }
+
+    #[test]
+    fn test_nearest_users() {
+        let knn = UserKnn::new(&fake_data(), Weighting::Binary);
+        let cosine = knn.nearest_users("user1", UserSimilarity::Cosine, 10);
+        assert_eq!(cosine.iter().map(|(user, _)| user.as_str()).collect::<Vec<&str>>(), vec!["user2", "user3"]);
+        //user1 = {S1, S2}, user2 = {S1, S2, S3}: Jaccard is 2 / 3
+        let jaccard = knn.nearest_users("user1", UserSimilarity::Jaccard, 1);
+        assert!((jaccard[0].1 - 2.0 / 3.0).abs() < 1e-12);
+        assert!(knn.nearest_users("nobody", UserSimilarity::Cosine, 10).is_empty());
+    }
+
+    #[test]
+    fn test_recommend() {
+        let knn = UserKnn::new(&fake_data(), Weighting::Binary);
+        let recommended = knn.recommend("user1", UserSimilarity::Jaccard, 2, &Exclusions::new(), &Ranker::default(), 10);
+        //S3 comes from the closest neighbour, nothing user1 already heard comes back
+        assert_eq!(recommended[0].0, "S3");
+        assert!(recommended.iter().all(|(song, _)| song != "S1" && song != "S2"));
+        //with one neighbour only user2's songs are used
+        assert_eq!(knn.recommend("user1", UserSimilarity::Jaccard, 1, &Exclusions::new(), &Ranker::default(), 10).len(), 1);
+        //an excluded song is never returned, the rest keep their order
+        let without_s3 = knn.recommend("user1", UserSimilarity::Jaccard, 2, &Exclusions::new().with_songs(["S3"]), &Ranker::default(), 10);
+        assert!(without_s3.iter().all(|(song, _)| song != "S3"));
+        assert_eq!(without_s3.len(), recommended.len() - 1);
+    }
+}
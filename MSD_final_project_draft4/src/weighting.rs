@@ -0,0 +1,120 @@
+use crate::csv_reader::MSD;
+use crate::exclusions::Exclusions;
+use crate::scoring::{top_scores, Ranker};
+use std::collections::{HashMap, HashSet};
+
+//how a user's listen_count for a song is turned into a weight
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weighting {
+    Raw,             //the listen_count itself
+    Log,             //log(1 + listen_count), so heavy repeat listeners do not drown everyone else out
+    Binary,          //1 if the user listened at all (same as the unweighted co-listen count)
+    Confidence(f64), //1 + alpha * listen_count, the confidence weight used by implicit-feedback models
+}
+
+impl Weighting {
+    pub fn weight(&self, plays: u64) -> f64 {
+        let plays = plays as f64;
+        match self {
+            Weighting::Raw => plays,
+            Weighting::Log => (1.0 + plays).ln(),
+            Weighting::Binary => 1.0,
+            Weighting::Confidence(alpha) => 1.0 + alpha * plays,
+        }
+    }
+}
+
+//function to find each user's weighted songs (like users_to_songs, but keeps how much they listened)
+//returns user id -> (song_id -> weight)
+pub fn users_to_weighted_songs(users: &HashSet<String>, data: &[MSD], weighting: Weighting) -> HashMap<String, HashMap<String, f64>> {
+    let mut plays: HashMap<String, HashMap<String, u64>> = HashMap::new(); //plays are added up first in case a song has several rows
+    for record in data {
+        if users.contains(&record.user_id) {
+            *plays.entry(record.user_id.clone()).or_default().entry(record.song_id.clone()).or_insert(0) += record.plays();
+        }
+    }
+
+    plays
+        .into_iter()
+        .map(|(user, songs)| {
+            let weighted = songs.into_iter().map(|(song, count)| (song, weighting.weight(count))).collect();
+            (user, weighted)
+        })
+        .collect()
+}
+
+//function to score songs by weighted co-listens with the seed song
+//each seed listener adds weight(seed) * weight(song), so with Weighting::Binary this is the plain listener count
+//excluded songs are left out. Returns up to k (song_id, weighted score) pairs, best first
+pub fn weighted_song_scores(seed_song_id: &str, data: &[MSD], weighting: Weighting, exclusions: &Exclusions, ranker: &Ranker, k: usize) -> Vec<(String, f64)> {
+    let users: HashSet<String> = data.iter().filter(|record| record.song_id == seed_song_id).map(|record| record.user_id.clone()).collect();
+    let user_songs = users_to_weighted_songs(&users, data, weighting);
+
+    let mut song_score: HashMap<String, f64> = HashMap::new();
+    let mut listeners: Vec<&String> = user_songs.keys().collect();
+    listeners.sort(); //float scores are added up in the same order every run, so they come out exactly the same
+    for user in listeners {
+        let songs = &user_songs[user];
+        let seed_weight = songs[seed_song_id]; //every user here listened to the seed
+        for (song, weight) in songs {
+            if song != seed_song_id && !exclusions.excludes_song(song) {
+                *song_score.entry(song.clone()).or_insert(0.0) += seed_weight * weight;
+            }
+        }
+    }
+    top_scores(song_score, ranker, k)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exclusions::Exclusions;
+    use crate::test_data::record;
+
+    #[test]
+    fn test_weighting() {
+        assert_eq!(Weighting::Raw.weight(500), 500.0);
+        assert_eq!(Weighting::Binary.weight(500), 1.0);
+        assert!((Weighting::Log.weight(1) - 2f64.ln()).abs() < 1e-12);
+        assert_eq!(Weighting::Confidence(40.0).weight(2), 81.0);
+    }
+
+    #[test]
+    fn test_weighted_song_scores() {
+        let data = vec![
+            record("user1", "S1", "A1", "Seed", 1),
+            record("user1", "S2", "A1", "Played Once", 1),
+            record("user2", "S1", "A1", "Seed", 1),
+            record("user2", "S2", "A1", "Played Once", 1),
+            record("user3", "S1", "A1", "Seed", 1),
+            record("user3", "S3", "A2", "On Repeat", 500),
+        ];
+
+        //binary weights: S2 has two co-listeners, S3 has one
+        let binary = weighted_song_scores("S1", &data, Weighting::Binary, &Exclusions::new(), &Ranker::default(), 10);
+        assert_eq!(binary, vec![("S2".to_string(), 2.0), ("S3".to_string(), 1.0)]);
+
+        //raw weights: the single user who played S3 500 times wins
+        let raw = weighted_song_scores("S1", &data, Weighting::Raw, &Exclusions::new(), &Ranker::default(), 10);
+        assert_eq!(raw[0], ("S3".to_string(), 500.0));
+        //excluding S3's artist leaves only S2
+        let no_a2 = weighted_song_scores("S1", &data, Weighting::Raw, &Exclusions::new().with_artists(["A2"], &data), &Ranker::default(), 10);
+        assert_eq!(no_a2, vec![("S2".to_string(), 2.0)]);
+    }
+
+    #[test]
+    fn test_weighted_song_scores_deterministic() {
+        //many listeners with uneven log weights, so adding them up in a different order would change the last bits
+        let mut data = Vec::new();
+        for user in 0..50 {
+            data.push(record(&format!("user{}", user), "S1", "A1", "Seed", user % 7 + 1));
+            data.push(record(&format!("user{}", user), "S2", "A1", "Other", user % 5 + 1));
+            data.push(record(&format!("user{}", user), "S3", "A1", "Third", user % 3 + 1));
+        }
+        let first = weighted_song_scores("S1", &data, Weighting::Log, &Exclusions::new(), &Ranker::default(), 10);
+        data.reverse(); //same data in another order, and a fresh HashMap with its own iteration order
+        for _ in 0..5 {
+            assert_eq!(weighted_song_scores("S1", &data, Weighting::Log, &Exclusions::new(), &Ranker::default(), 10), first);
+        }
+    }
+}